@@ -0,0 +1,124 @@
+use super::{server_info::client, store};
+use anyhow::Result;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// An in-game persona linked to a discord user.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Account {
+    pub name: String,
+    pub persona_id: u64,
+    /// game as used by the gametools api, like bf1
+    pub game: String,
+    pub platform: String,
+}
+
+impl Account {
+    fn same_game(&self, game: &str, platform: &str) -> bool {
+        self.game == game && self.platform == platform
+    }
+}
+
+#[derive(Deserialize)]
+struct PlayerResponse {
+    #[serde(rename = "userName")]
+    name: String,
+    id: u64,
+}
+
+/// Look the player up on gametools, none if it doesn't exist.
+pub async fn lookup(game: &str, platform: &str, name: &str) -> Result<Option<Account>> {
+    let mut url =
+        Url::parse(&format!("https://api.gametools.network/{}/player/", game)[..]).unwrap();
+    url.query_pairs_mut()
+        .append_pair("name", name)
+        .append_pair("platform", platform)
+        .append_pair("skip_battlelog", "true");
+    let response = client()
+        .get(url)
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    if response.get("errors").is_some() {
+        return Ok(None);
+    }
+    let player = serde_json::from_value::<PlayerResponse>(response)?;
+    Ok(Some(Account {
+        name: player.name,
+        persona_id: player.id,
+        game: game.to_string(),
+        platform: platform.to_string(),
+    }))
+}
+
+/// Linked accounts per discord user, saved to disk on every change.
+pub struct Accounts {
+    path: String,
+    links: HashMap<u64, Vec<Account>>,
+}
+
+impl Accounts {
+    pub fn load(path: &str) -> Accounts {
+        Accounts {
+            path: path.to_string(),
+            links: store::load(path),
+        }
+    }
+
+    /// Discord user the persona is linked to.
+    pub fn owner(&self, account: &Account) -> Option<u64> {
+        self.links
+            .iter()
+            .find(|(_, accounts)| {
+                accounts.iter().any(|linked| {
+                    linked.persona_id == account.persona_id
+                        && linked.same_game(&account.game, &account.platform)
+                })
+            })
+            .map(|(user, _)| *user)
+    }
+
+    /// Users with their account for the game and platform.
+    pub fn linked(&self, game: &str, platform: &str) -> Vec<(u64, Account)> {
+        self.links
+            .iter()
+            .flat_map(|(user, accounts)| {
+                accounts
+                    .iter()
+                    .filter(|account| account.same_game(game, platform))
+                    .map(move |account| (*user, account.clone()))
+            })
+            .collect()
+    }
+
+    /// Link the account to the user, replacing their account for the same
+    /// game and platform and taking it away from anyone else it was linked to.
+    pub fn link(&mut self, user: u64, account: Account) {
+        for accounts in self.links.values_mut() {
+            accounts.retain(|linked| {
+                !(linked.persona_id == account.persona_id
+                    && linked.same_game(&account.game, &account.platform))
+            });
+        }
+        let accounts = self.links.entry(user).or_default();
+        accounts.retain(|linked| !linked.same_game(&account.game, &account.platform));
+        accounts.push(account);
+        self.links.retain(|_, accounts| !accounts.is_empty());
+        store::save(&self.path, &self.links);
+    }
+
+    /// Remove the account of the user for a game and platform, returns it if
+    /// there was one.
+    pub fn unlink(&mut self, user: u64, game: &str, platform: &str) -> Option<Account> {
+        let accounts = self.links.get_mut(&user)?;
+        let index = accounts
+            .iter()
+            .position(|linked| linked.same_game(game, platform))?;
+        let account = accounts.remove(index);
+        self.links.retain(|_, accounts| !accounts.is_empty());
+        store::save(&self.path, &self.links);
+        Some(account)
+    }
+}
@@ -0,0 +1,106 @@
+use super::{
+    message,
+    players::{Player, PlayerList},
+    server_info::{ServerInfo, ServerState},
+    state::State,
+};
+use chrono::Utc;
+use serenity::{
+    builder::{CreateEmbed, CreateMessage},
+    client::Context,
+    model::id::ChannelId,
+};
+
+/// Which admins are on the server and since when it went without one.
+#[derive(Default)]
+pub struct Presence {
+    /// names of the admins on the server, none before the first player list
+    online: Option<Vec<String>>,
+    /// when the server went live without an admin on it
+    absent_since: Option<i64>,
+    alerted: bool,
+}
+
+fn is_admin(statics: &message::Static, player: &Player) -> bool {
+    statics.admins.iter().any(|admin| {
+        admin.eq_ignore_ascii_case(&player.name)
+            || player
+                .player_id
+                .map(|id| &id.to_string() == admin)
+                .unwrap_or(false)
+    })
+}
+
+/// Admins on the server, none if no admins are configured or there is no
+/// player list yet.
+pub fn online(state: &State) -> Option<Vec<String>> {
+    match state.statics().admins.is_empty() {
+        true => None,
+        false => state.admins.lock().unwrap().online.clone(),
+    }
+}
+
+/// Short text for the presence of the bot.
+pub fn describe(state: &State) -> Option<String> {
+    online(state).map(|online| match online.is_empty() {
+        true => "no admin on".to_string(),
+        false => "admin on".to_string(),
+    })
+}
+
+async fn alert(ctx: &Context, statics: &message::Static, status: &ServerInfo) {
+    let embed = CreateEmbed::new()
+        .title("No admin on the server")
+        .description(format!(
+            "{} is live with {} players and no admin has been on for {} minutes",
+            statics.server_name, status.detailed.current_players, statics.admin_absence_mins
+        ));
+    if let Err(e) = ChannelId::new(statics.admin_channel)
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await
+    {
+        log::error!("Failed to send admin absence alert: {:#?}", e);
+    }
+}
+
+/// Keep track of the admins on the server and alert the admin channel once
+/// when it has been live too long without one.
+pub async fn update(ctx: &Context, state: &State, status: &ServerInfo, list: &PlayerList) {
+    let statics = state.statics();
+    if statics.admins.is_empty() {
+        return;
+    }
+    let now = Utc::now().timestamp();
+    let online: Vec<String> = list
+        .all()
+        .filter(|player| is_admin(&statics, player))
+        .map(|player| player.display_name())
+        .collect();
+    let live = matches!(
+        ServerState::from_status(status, &statics),
+        ServerState::Live | ServerState::Full
+    );
+    let should_alert = {
+        let mut presence = state.admins.lock().unwrap();
+        let absent = live && online.is_empty();
+        presence.online = Some(online);
+        match absent {
+            true => {
+                let since = *presence.absent_since.get_or_insert(now);
+                let due = !presence.alerted && now - since >= statics.admin_absence_mins * 60;
+                if due {
+                    presence.alerted = true;
+                }
+                due
+            }
+            false => {
+                presence.absent_since = None;
+                presence.alerted = false;
+                false
+            }
+        }
+    };
+    if should_alert && statics.admin_channel != 0 {
+        alert(ctx, &statics, status).await;
+    }
+}
@@ -0,0 +1,1152 @@
+use super::{
+    accounts, admins, digest, graph, guilds, members, message, notify, players, roles,
+    server_info::{self, api_game},
+    settings,
+    state::State,
+    watch,
+};
+use anyhow::Result;
+use serenity::{
+    all::{CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue},
+    builder::{
+        CreateAttachment, CreateAutocompleteResponse, CreateCommand, CreateCommandOption,
+        CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse,
+    },
+    client::Context,
+    model::{application::Command, id::GuildId, Permissions},
+};
+
+/// Game and platform options for commands about in-game accounts.
+fn account_options(command: CreateCommand) -> CreateCommand {
+    let mut game = CreateCommandOption::new(
+        CommandOptionType::String,
+        "game",
+        "the game (defaults to the game of the server)",
+    )
+    .required(false);
+    for name in ["bf1", "bfv", "bf2042", "bf4", "bf3", "bfh"] {
+        game = game.add_string_choice(name, name);
+    }
+    let mut platform = CreateCommandOption::new(
+        CommandOptionType::String,
+        "platform",
+        "the platform (defaults to the platform of the server)",
+    )
+    .required(false);
+    for name in ["pc", "ps4", "ps5", "xboxone", "xboxseries"] {
+        platform = platform.add_string_choice(name, name);
+    }
+    command.add_option(game).add_option(platform).add_option(
+        CreateCommandOption::new(
+            CommandOptionType::User,
+            "user",
+            "change someone else's link (needs manage server)",
+        )
+        .required(false),
+    )
+}
+
+/// `/config` with a subcommand per setting of the discord server.
+fn config_command() -> CreateCommand {
+    let mut language = CreateCommandOption::new(
+        CommandOptionType::String,
+        "language",
+        "language of the alerts",
+    )
+    .required(true);
+    for (code, name) in guilds::LANGUAGES {
+        language = language.add_string_choice(name, code);
+    }
+    let mut alert =
+        CreateCommandOption::new(CommandOptionType::String, "alert", "the alert").required(true);
+    for rule in guilds::Alert::ALL {
+        alert = alert.add_string_choice(rule.name(), rule.name());
+    }
+    CreateCommand::new("config")
+        .description("Settings of the bot for this server")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .dm_permission(false)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "show",
+            "Show the settings of this server",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "channel",
+                "Channel to post the alerts in",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Channel, "channel", "the channel")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "role",
+                "Role to ping with the seeding or queue alerts",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "alerts", "which alerts")
+                    .add_string_choice("seeding", "seeding")
+                    .add_string_choice("queue", "queue")
+                    .required(true),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Role,
+                    "role",
+                    "the role (leave empty to not ping)",
+                )
+                .required(false),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "language",
+                "Language of the alerts",
+            )
+            .add_sub_option(language),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "alert",
+                "Turn an alert on or off",
+            )
+            .add_sub_option(alert)
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Boolean, "enabled", "post this alert")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "set",
+                "Change a setting of the bot, only in the server of the bot",
+            )
+            .add_sub_option(setting_option())
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "value", "the new value")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reset",
+                "Go back to the env value of a setting, only in the server of the bot",
+            )
+            .add_sub_option(setting_option()),
+        )
+}
+
+/// The settings of the bot `/config set` can change.
+fn setting_option() -> CreateCommandOption {
+    let mut option = CreateCommandOption::new(CommandOptionType::String, "setting", "the setting")
+        .required(true);
+    for (name, _) in settings::SETTINGS {
+        option = option.add_string_choice(name, name);
+    }
+    option
+}
+
+fn commands() -> Vec<CreateCommand> {
+    vec![
+        CreateCommand::new("heatmap")
+            .description("Average players per hour of the week")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "weeks",
+                    "only use the last few weeks (defaults to all history)",
+                )
+                .min_int_value(1)
+                .max_int_value(520)
+                .required(false),
+            ),
+        CreateCommand::new("status").description("Current players, map and forecast"),
+        CreateCommand::new("players")
+            .description("Players on the server per team")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "image",
+                    "show all teams as a single image",
+                )
+                .required(false),
+            ),
+        CreateCommand::new("top")
+            .description("Players with the most time on the server")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "period",
+                    "the last week, month or all time (defaults to week)",
+                )
+                .add_string_choice("week", "week")
+                .add_string_choice("month", "month")
+                .add_string_choice("all time", "all")
+                .required(false),
+            ),
+        CreateCommand::new("seen")
+            .description("When a player was last on the server and for how long in total")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "player", "player name")
+                    .set_autocomplete(true)
+                    .required(true),
+            ),
+        CreateCommand::new("rotation").description("Recent maps and the likely next map"),
+        CreateCommand::new("notify")
+            .description("Get a message when the server plays a map")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "map", "the map")
+                    .set_autocomplete(true)
+                    .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "mode",
+                    "only for this mode (defaults to any)",
+                )
+                .set_autocomplete(true)
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "players",
+                    "wait until there are at least this many players",
+                )
+                .min_int_value(0)
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Role,
+                    "role",
+                    "ping a role in the alert channel instead of a dm (needs manage roles)",
+                )
+                .required(false),
+            ),
+        account_options(
+            CreateCommand::new("link")
+                .description("Link your in-game account")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "name", "your player name")
+                        .required(true),
+                ),
+        ),
+        account_options(
+            CreateCommand::new("unlink").description("Remove the link to your in-game account"),
+        ),
+        CreateCommand::new("watch")
+            .description("Get a message when a player joins or leaves the server")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "player", "player name or id")
+                    .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "announce",
+                    "post in the watch channel instead of a dm (needs manage server)",
+                )
+                .required(false),
+            ),
+        CreateCommand::new("unwatch")
+            .description("Stop the messages for a player")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "player", "player name or id")
+                    .set_autocomplete(true)
+                    .required(true),
+            ),
+        CreateCommand::new("rolebuttons")
+            .description("Post the buttons to get pinged for alerts in this channel")
+            .default_member_permissions(Permissions::MANAGE_ROLES)
+            .dm_permission(false),
+        config_command(),
+        CreateCommand::new("unnotify")
+            .description("Stop the messages for a map")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "map", "the map")
+                    .set_autocomplete(true)
+                    .required(true),
+            ),
+    ]
+}
+
+/// Register the slash commands globally, so the other servers the bot is in
+/// can use them too.
+pub async fn register(ctx: &Context, state: &State) {
+    if let Err(e) = Command::set_global_commands(&ctx.http, commands()).await {
+        log::error!("Failed to register commands: {:#?}", e);
+    }
+    // older versions registered them in the guild only, they would show up twice
+    let guild = state.statics().guild;
+    if guild != 0 {
+        if let Err(e) = GuildId::new(guild)
+            .set_commands(&ctx.http, Vec::new())
+            .await
+        {
+            log::error!("Failed to remove the guild commands: {:#?}", e);
+        }
+    }
+}
+
+fn integer_option(command: &CommandInteraction, name: &str) -> Option<i64> {
+    command
+        .data
+        .options()
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match option.value {
+            ResolvedValue::Integer(value) => Some(value),
+            _ => None,
+        })
+}
+
+fn string_option<'a>(command: &'a CommandInteraction, name: &str) -> Option<&'a str> {
+    command
+        .data
+        .options()
+        .into_iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match option.value {
+            ResolvedValue::String(value) => Some(value),
+            _ => None,
+        })
+}
+
+/// Name and options of the subcommand that was used.
+fn subcommand<'a>(command: &'a CommandInteraction) -> Option<(&'a str, Vec<ResolvedOption<'a>>)> {
+    command
+        .data
+        .options()
+        .into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::SubCommand(options) => Some((option.name, options)),
+            _ => None,
+        })
+}
+
+/// Permissions of the member that ran the command, none in dms.
+fn permissions(command: &CommandInteraction) -> Permissions {
+    command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .unwrap_or_else(Permissions::empty)
+}
+
+/// The user an account command is about, the `user` option is only allowed
+/// for admins.
+fn account_user(command: &CommandInteraction) -> Result<u64, String> {
+    let other = command
+        .data
+        .options()
+        .into_iter()
+        .find(|option| option.name == "user")
+        .and_then(|option| match option.value {
+            ResolvedValue::User(user, _) => Some(user.id.get()),
+            _ => None,
+        });
+    match other {
+        Some(user) if user != command.user.id.get() => match permissions(command).manage_guild() {
+            true => Ok(user),
+            false => Err(
+                "You need the manage server permission to change someone else's link".to_string(),
+            ),
+        },
+        _ => Ok(command.user.id.get()),
+    }
+}
+
+async fn reply(ctx: &Context, command: &CommandInteraction, content: String) -> Result<()> {
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn heatmap(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let png = graph::heatmap_png(state, integer_option(command, "weeks"))?;
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .add_file(CreateAttachment::bytes(png, "heatmap.png")),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn status(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let status = state.current_status();
+    let map = state.map_image.read().unwrap().clone();
+    let (status, map) = match (status, map) {
+        (Some(status), Some(map)) => (status, map),
+        _ => {
+            command
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("The server can't be found right now")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    let samples = state.history.read().unwrap().recent_players(3, 60);
+    let card = graph::encode_png(server_info::banner_card(
+        &map,
+        &status,
+        &state.statics(),
+        &samples,
+    ))?;
+
+    let forecast = state.forecast();
+    let mut embed = CreateEmbed::new()
+        .title(&status.detailed.server_name)
+        .url(message::server_link(
+            &state.statics(),
+            &status.game_id.clone().unwrap_or_default(),
+        ))
+        .field(
+            "Players",
+            format!(
+                "{}/{}",
+                status.detailed.current_players, status.detailed.max_players
+            ),
+            true,
+        )
+        .field(
+            "Queue",
+            status.detailed.in_que.unwrap_or(0).to_string(),
+            true,
+        )
+        .field(
+            "Map",
+            format!(
+                "{} - {}",
+                status.detailed.server_map, status.detailed.map_mode
+            ),
+            true,
+        )
+        .field(
+            "Full in",
+            match forecast.full_in {
+                Some(minutes) => format!("~{} minutes", minutes),
+                None => "-".to_string(),
+            },
+            true,
+        )
+        .field(
+            "Queue wait",
+            match forecast.queue_wait {
+                Some(minutes) => format!("~{} minutes", minutes),
+                None => "-".to_string(),
+            },
+            true,
+        )
+        .image("attachment://status.png");
+    let list = state.players.read().unwrap().clone();
+    if let Some(list) = list {
+        let playing = members::playing(state, &list);
+        embed = embed.field(
+            "Members",
+            format!(
+                "{} of our members {} playing",
+                playing.len(),
+                match playing.len() {
+                    1 => "is",
+                    _ => "are",
+                }
+            ),
+            true,
+        );
+    }
+    if let Some(online) = admins::online(state) {
+        embed = embed.field(
+            "Admins",
+            match online.is_empty() {
+                true => "none on the server".to_string(),
+                false => online.join(", "),
+            },
+            true,
+        );
+    }
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .add_file(CreateAttachment::bytes(card, "status.png")),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn players(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    if !players::supported(&state.statics()) {
+        return reply(
+            ctx,
+            command,
+            "There is no player list for this game".to_string(),
+        )
+        .await;
+    }
+    let status = match state.current_status() {
+        Some(status) => status,
+        None => {
+            return reply(
+                ctx,
+                command,
+                "The server can't be found right now".to_string(),
+            )
+            .await
+        }
+    };
+    let image = command.data.options().iter().any(|option| {
+        option.name == "image" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+    command.defer(&ctx.http).await?;
+
+    let list = players::fetch(&state.statics(), &status).await;
+    let response = match list {
+        Err(e) => {
+            log::error!("Failed to get the player list: {:#?}", e);
+            EditInteractionResponse::new().content("Couldn't get the player list, try again later")
+        }
+        Ok(list) if image => {
+            let png = graph::encode_png(graph::scoreboard(&list, &status.detailed.server_name))?;
+            EditInteractionResponse::new()
+                .new_attachment(CreateAttachment::bytes(png, "players.png"))
+        }
+        Ok(list) => {
+            let (embed, buttons) = players::page(&list, 0, &status);
+            EditInteractionResponse::new()
+                .embed(embed)
+                .components(vec![buttons])
+        }
+    };
+    command.edit_response(&ctx.http, response).await?;
+    Ok(())
+}
+
+async fn top(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let period = string_option(command, "period").unwrap_or("week");
+    let now = chrono::Utc::now().timestamp();
+    let (since, title) = match period {
+        "month" => (
+            Some(now - 30 * 24 * 60 * 60),
+            "Regulars of the last 30 days",
+        ),
+        "all" => (None, "Regulars of all time"),
+        _ => (Some(now - 7 * 24 * 60 * 60), "Regulars of the last 7 days"),
+    };
+    let lines = state
+        .playtime
+        .read()
+        .unwrap()
+        .top(since, 15)
+        .iter()
+        .enumerate()
+        .map(|(i, (regular, secs))| {
+            format!(
+                "{}. {} - {}",
+                i + 1,
+                regular.name,
+                digest::format_duration(*secs)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let embed = CreateEmbed::new()
+        .title(title)
+        .description(match lines.is_empty() {
+            true => "Nobody played yet".to_string(),
+            false => lines,
+        })
+        .footer(CreateEmbedFooter::new(&state.statics().server_name));
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().embed(embed),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn seen(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let name = string_option(command, "player").unwrap_or_default();
+    let regular = state.playtime.read().unwrap().get(name).cloned();
+    let regular = match regular {
+        Some(regular) => regular,
+        None => {
+            return reply(
+                ctx,
+                command,
+                format!("{} hasn't been seen on the server", name),
+            )
+            .await
+        }
+    };
+    let now = chrono::Utc::now().timestamp();
+    let embed = CreateEmbed::new()
+        .title(&regular.name)
+        .field(
+            "Last seen",
+            match now - regular.last_seen <= 2 * 60 {
+                true => "on the server now".to_string(),
+                false => format!("<t:{}:R>", regular.last_seen),
+            },
+            true,
+        )
+        .field(
+            "Total time",
+            digest::format_duration(regular.total_secs),
+            true,
+        )
+        .field("Sessions", regular.session_count.to_string(), true)
+        .field(
+            "Last 7 days",
+            digest::format_duration(regular.played_since(now - 7 * 24 * 60 * 60)),
+            true,
+        )
+        .field("First seen", format!("<t:{}:D>", regular.first_seen), true);
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().embed(embed),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn rotation(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let embed = {
+        let rotation = state.rotation.read().unwrap();
+        let recent = rotation
+            .recent(8)
+            .map(|change| format!("<t:{}:t> {}", change.timestamp, change.name()))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let next = match rotation.predict() {
+            Some(prediction) => match prediction.eta {
+                Some(eta) => format!("{} <t:{}:R>", prediction.next, eta),
+                None => prediction.next,
+            },
+            None => "not enough history yet".to_string(),
+        };
+        let lengths = rotation.round_lengths();
+        let current = match rotation.current() {
+            Some(current) => format!(
+                "{} since <t:{}:t>{}",
+                current.name(),
+                current.timestamp,
+                match lengths.get(&current.name()) {
+                    Some(length) => format!(
+                        ", rounds take {} on average",
+                        digest::format_duration(*length)
+                    ),
+                    None => "".to_string(),
+                }
+            ),
+            None => "-".to_string(),
+        };
+        CreateEmbed::new()
+            .title("Map rotation")
+            .description(&state.statics().server_name)
+            .field("Current", current, false)
+            .field("Likely next", next, false)
+            .field(
+                "Recent maps",
+                match recent.is_empty() {
+                    true => "-".to_string(),
+                    false => recent,
+                },
+                false,
+            )
+    };
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().embed(embed),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn notify(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let role = command
+        .data
+        .options()
+        .into_iter()
+        .find(|option| option.name == "role")
+        .and_then(|option| match option.value {
+            ResolvedValue::Role(role) => Some(role.id.get()),
+            _ => None,
+        });
+    if role.is_some() && !permissions(command).manage_roles() {
+        return reply(
+            ctx,
+            command,
+            "You need the manage roles permission to ping a role".to_string(),
+        )
+        .await;
+    }
+    let guild = command.guild_id.map(|guild| guild.get());
+    if role.is_some() && state.guild_config(guild).channel == 0 {
+        return reply(
+            ctx,
+            command,
+            "Set the alert channel with `/config channel` to ping a role".to_string(),
+        )
+        .await;
+    }
+
+    let subscription = notify::Subscription {
+        user: command.user.id.get(),
+        role,
+        guild,
+        map: string_option(command, "map")
+            .unwrap_or_default()
+            .to_string(),
+        mode: string_option(command, "mode").map(str::to_string),
+        players: integer_option(command, "players")
+            .map(|players| players as i32)
+            .unwrap_or(state.statics().min_player_amount),
+        notified_round: 0,
+    };
+    let content = {
+        let mut subscriptions = state.subscriptions.write().unwrap();
+        subscriptions.add(subscription);
+        let list = subscriptions
+            .of_user(command.user.id.get())
+            .iter()
+            .map(|sub| format!("- {}", sub.describe()))
+            .collect::<Vec<String>>()
+            .join("\n");
+        format!("You'll be notified for:\n{}", list)
+    };
+    reply(ctx, command, content).await
+}
+
+async fn unnotify(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let map = string_option(command, "map").unwrap_or_default();
+    let removed = state
+        .subscriptions
+        .write()
+        .unwrap()
+        .remove(command.user.id.get(), map);
+    let content = match removed {
+        0 => format!("You weren't notified for {}", map),
+        _ => format!("You won't be notified for {} anymore", map),
+    };
+    reply(ctx, command, content).await
+}
+
+async fn link(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let user = match account_user(command) {
+        Ok(user) => user,
+        Err(message) => return reply(ctx, command, message).await,
+    };
+    let statics = state.statics();
+    let game = string_option(command, "game").unwrap_or(api_game(&statics.game));
+    let platform = string_option(command, "platform").unwrap_or(&statics.platform);
+    let name = string_option(command, "name").unwrap_or_default();
+    // the lookup can take longer than the 3 seconds discord waits for a reply
+    command.defer_ephemeral(&ctx.http).await?;
+
+    let content = match accounts::lookup(game, platform, name).await {
+        Err(e) => {
+            log::error!("Failed to look up player {}: {:#?}", name, e);
+            "Couldn't reach gametools, try again later".to_string()
+        }
+        Ok(None) => format!("Couldn't find {} on {} {}", name, game, platform),
+        Ok(Some(account)) => {
+            let mut accounts = state.accounts.write().unwrap();
+            match accounts.owner(&account) {
+                Some(owner) if owner != user && user == command.user.id.get() => format!(
+                    "{} is already linked to <@{}>, ask an admin to change it",
+                    account.name, owner
+                ),
+                _ => {
+                    let content = format!(
+                        "Linked <@{}> to {} on {} {}",
+                        user, account.name, game, platform
+                    );
+                    accounts.link(user, account);
+                    content
+                }
+            }
+        }
+    };
+    command
+        .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+        .await?;
+    Ok(())
+}
+
+async fn unlink(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let user = match account_user(command) {
+        Ok(user) => user,
+        Err(message) => return reply(ctx, command, message).await,
+    };
+    let statics = state.statics();
+    let game = string_option(command, "game").unwrap_or(api_game(&statics.game));
+    let platform = string_option(command, "platform").unwrap_or(&statics.platform);
+    let removed = state.accounts.write().unwrap().unlink(user, game, platform);
+    let content = match removed {
+        Some(account) => format!("Unlinked {} from <@{}>", account.name, user),
+        None => format!("<@{}> has no account linked on {} {}", user, game, platform),
+    };
+    reply(ctx, command, content).await
+}
+
+async fn watch(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let announce = command.data.options().iter().any(|option| {
+        option.name == "announce" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+    if announce && !permissions(command).manage_guild() {
+        return reply(
+            ctx,
+            command,
+            "You need the manage server permission to announce players".to_string(),
+        )
+        .await;
+    }
+    let content = {
+        let mut watchlist = state.watchlist.write().unwrap();
+        watchlist.add(watch::Watch {
+            player: string_option(command, "player")
+                .unwrap_or_default()
+                .to_string(),
+            user: command.user.id.get(),
+            announce,
+        });
+        let list = watchlist
+            .of_user(command.user.id.get())
+            .iter()
+            .map(|watch| match watch.announce {
+                true => format!("- {} (announced)", watch.player),
+                false => format!("- {}", watch.player),
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        format!("You're watching:\n{}", list)
+    };
+    reply(ctx, command, content).await
+}
+
+async fn unwatch(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let player = string_option(command, "player").unwrap_or_default();
+    let content = match state
+        .watchlist
+        .write()
+        .unwrap()
+        .remove(command.user.id.get(), player)
+    {
+        true => format!("You stopped watching {}", player),
+        false => format!("You weren't watching {}", player),
+    };
+    reply(ctx, command, content).await
+}
+
+async fn role_buttons(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let config = state.guild_config(command.guild_id.map(|guild| guild.get()));
+    let buttons = match roles::buttons(&config) {
+        Some(buttons) => buttons,
+        None => {
+            return reply(
+                ctx,
+                command,
+                "Set the seeding or queue role with `/config role` to use the buttons".to_string(),
+            )
+            .await
+        }
+    };
+    // a normal message instead of the response, so it stays after the
+    // interaction expires
+    command
+        .channel_id
+        .send_message(
+            &ctx.http,
+            CreateMessage::new()
+                .content(format!(
+                    "Get pinged when {} needs players",
+                    state.statics().server_name
+                ))
+                .components(vec![buttons]),
+        )
+        .await?;
+    reply(ctx, command, "Posted the buttons".to_string()).await
+}
+
+/// Text of the `/config show` embed for the settings of a guild.
+fn describe_config(config: &guilds::GuildConfig) -> String {
+    let mention = |id: u64, mention: fn(u64) -> String| match id {
+        0 => "none".to_string(),
+        id => mention(id),
+    };
+    format!(
+        "Alert channel: {}\nSeeding role: {}\nQueue role: {}\nLanguage: {}\nAlerts: {}",
+        mention(config.channel, |id| format!("<#{}>", id)),
+        mention(config.seeding_role, |id| format!("<@&{}>", id)),
+        mention(config.queue_role, |id| format!("<@&{}>", id)),
+        config.lang,
+        guilds::Alert::ALL
+            .iter()
+            .map(|alert| match config.enabled(*alert) {
+                true => format!("{} on", alert.name()),
+                false => format!("{} off", alert.name()),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+async fn config(ctx: &Context, state: &State, command: &CommandInteraction) -> Result<()> {
+    let guild = match command.guild_id {
+        Some(guild) => guild.get(),
+        None => return reply(ctx, command, "Use this in a server".to_string()).await,
+    };
+    if !permissions(command).manage_guild() {
+        return reply(
+            ctx,
+            command,
+            "You need the manage server permission for this".to_string(),
+        )
+        .await;
+    }
+    let (name, options) = match subcommand(command) {
+        Some(subcommand) => subcommand,
+        None => return Ok(()),
+    };
+    let option = |name: &str| {
+        options
+            .iter()
+            .find(|option| option.name == name)
+            .map(|option| &option.value)
+    };
+    let content = match name {
+        "set" | "reset" if state.statics().guild == 0 => {
+            "Set `guild` to the id of the server of the bot to change its settings".to_string()
+        }
+        "set" | "reset" if state.statics().guild != guild => {
+            "The settings of the bot can only be changed in its own server".to_string()
+        }
+        "set" | "reset" => {
+            let setting = match option("setting") {
+                Some(ResolvedValue::String(setting)) => *setting,
+                _ => return Ok(()),
+            };
+            let result = match option("value") {
+                Some(ResolvedValue::String(value)) if name == "set" => {
+                    state.set_setting(setting, value)
+                }
+                _ => state.reset_setting(setting),
+            };
+            match result {
+                Ok(()) => format!(
+                    "{} is now {}",
+                    setting,
+                    settings::get(&state.statics(), setting)
+                ),
+                Err(e) => e,
+            }
+        }
+        "channel" => match option("channel") {
+            Some(ResolvedValue::Channel(channel)) => {
+                let id = channel.id.get();
+                state
+                    .guilds
+                    .write()
+                    .unwrap()
+                    .update(&state.statics(), guild, |config| config.channel = id);
+                format!("Alerts will be posted in <#{}>", id)
+            }
+            _ => return Ok(()),
+        },
+        "role" => {
+            let role = match option("role") {
+                Some(ResolvedValue::Role(role)) => role.id.get(),
+                _ => 0,
+            };
+            let seeding = matches!(option("alerts"), Some(ResolvedValue::String("seeding")));
+            state
+                .guilds
+                .write()
+                .unwrap()
+                .update(&state.statics(), guild, |config| match seeding {
+                    true => config.seeding_role = role,
+                    false => config.queue_role = role,
+                });
+            match role {
+                0 => "Nobody will be pinged with these alerts".to_string(),
+                role => format!("<@&{}> will be pinged with these alerts", role),
+            }
+        }
+        "language" => match option("language") {
+            Some(ResolvedValue::String(lang)) => {
+                let lang = lang.to_string();
+                state
+                    .guilds
+                    .write()
+                    .unwrap()
+                    .update(&state.statics(), guild, |config| config.lang = lang.clone());
+                format!("Alerts will be posted in {}", lang)
+            }
+            _ => return Ok(()),
+        },
+        "alert" => {
+            let alert = match option("alert") {
+                Some(ResolvedValue::String(name)) => guilds::Alert::from_name(name),
+                _ => None,
+            };
+            let enabled = matches!(option("enabled"), Some(ResolvedValue::Boolean(true)));
+            match alert {
+                Some(alert) => {
+                    let statics = state.statics();
+                    state
+                        .guilds
+                        .write()
+                        .unwrap()
+                        .update(&statics, guild, |config| {
+                            config.disabled.retain(|disabled| *disabled != alert);
+                            if !enabled {
+                                config.disabled.push(alert);
+                            }
+                        });
+                    match enabled {
+                        true => format!("The {} alert is on", alert.name()),
+                        false => format!("The {} alert is off", alert.name()),
+                    }
+                }
+                None => return Ok(()),
+            }
+        }
+        _ => format!(
+            "**This server**\n{}\n\n**Bot settings**\n{}",
+            describe_config(&state.guild_config(Some(guild))),
+            state
+                .settings()
+                .iter()
+                .map(|(name, value, source)| format!("{}: {} ({})", name, value, source))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ),
+    };
+    reply(ctx, command, content).await
+}
+
+/// Suggest maps and modes seen in the history, players for `/seen`, or for
+/// `/unnotify` and `/unwatch` what the user is subscribed to.
+pub async fn autocomplete(ctx: &Context, state: &State, command: &CommandInteraction) {
+    let focused = match command.data.autocomplete() {
+        Some(focused) => focused,
+        None => return,
+    };
+    let mut values: Vec<String> = match (command.data.name.as_str(), focused.name) {
+        ("seen", _) => state.playtime.read().unwrap().search(focused.value),
+        ("unwatch", _) => state
+            .watchlist
+            .read()
+            .unwrap()
+            .of_user(command.user.id.get())
+            .iter()
+            .map(|watch| watch.player.clone())
+            .collect(),
+        ("unnotify", _) => state
+            .subscriptions
+            .read()
+            .unwrap()
+            .of_user(command.user.id.get())
+            .iter()
+            .map(|sub| sub.map.clone())
+            .collect(),
+        (_, "mode") => state
+            .history
+            .read()
+            .unwrap()
+            .since(0)
+            .iter()
+            .map(|sample| sample.mode.clone())
+            .collect(),
+        _ => state
+            .history
+            .read()
+            .unwrap()
+            .since(0)
+            .iter()
+            .map(|sample| sample.map.clone())
+            .collect(),
+    };
+    values.sort();
+    values.dedup();
+    let search = focused.value.to_lowercase();
+    let mut response = CreateAutocompleteResponse::new();
+    for value in values
+        .iter()
+        .filter(|value| !value.is_empty() && value.to_lowercase().contains(&search))
+        .take(25)
+    {
+        response = response.add_string_choice(value, value);
+    }
+    if let Err(e) = command
+        .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+        .await
+    {
+        log::error!("Failed to autocomplete /{}: {:#?}", command.data.name, e);
+    }
+}
+
+pub async fn handle(ctx: &Context, state: &State, command: &CommandInteraction) {
+    let result = match command.data.name.as_str() {
+        "heatmap" => heatmap(ctx, state, command).await,
+        "status" => status(ctx, state, command).await,
+        "rotation" => rotation(ctx, state, command).await,
+        "players" => players(ctx, state, command).await,
+        "top" => top(ctx, state, command).await,
+        "seen" => seen(ctx, state, command).await,
+        "notify" => notify(ctx, state, command).await,
+        "unnotify" => unnotify(ctx, state, command).await,
+        "rolebuttons" => role_buttons(ctx, state, command).await,
+        "link" => link(ctx, state, command).await,
+        "watch" => watch(ctx, state, command).await,
+        "unwatch" => unwatch(ctx, state, command).await,
+        "unlink" => unlink(ctx, state, command).await,
+        "config" => config(ctx, state, command).await,
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        log::error!("Failed to run /{}: {:#?}", command.data.name, e);
+    }
+}
@@ -0,0 +1,339 @@
+use super::{graph, history, message, server_info::ServerState, state::State};
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use serenity::{
+    builder::{CreateAttachment, CreateEmbed, CreateEmbedFooter, CreateMessage},
+    client::Context,
+    model::id::ChannelId,
+};
+use std::{collections::HashMap, sync::Arc};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Period {
+    Daily,
+    Weekly,
+}
+
+impl Period {
+    fn seconds(&self) -> i64 {
+        match self {
+            Period::Daily => 24 * 60 * 60,
+            Period::Weekly => 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Stats of the samples of a digest period.
+pub struct Summary {
+    /// most players and the timestamp it happened
+    pub peak: Option<(i32, i64)>,
+    pub average: f64,
+    pub full_secs: i64,
+    pub queue_secs: i64,
+    /// seconds played per map, most played first
+    pub maps: Vec<(String, i64)>,
+    pub longest_seeding_secs: i64,
+    pub uptime: Option<f64>,
+}
+
+/// How long every sample lasted until the next one, capped at 5 minutes so
+/// time the bot wasn't running doesn't count.
+fn durations(samples: &[history::Sample]) -> Vec<i64> {
+    let mut durations: Vec<i64> = samples
+        .windows(2)
+        .map(|pair| (pair[1].timestamp - pair[0].timestamp).clamp(0, 5 * 60))
+        .collect();
+    if !samples.is_empty() {
+        durations.push(60);
+    }
+    durations
+}
+
+pub fn summarize(samples: &[history::Sample]) -> Summary {
+    let durations = durations(samples);
+    let online: Vec<(&history::Sample, i64)> = samples
+        .iter()
+        .zip(durations.iter().copied())
+        .filter(|(sample, _)| sample.state != ServerState::Offline)
+        .collect();
+
+    let peak = online
+        .iter()
+        .max_by_key(|(sample, _)| sample.players)
+        .map(|(sample, _)| (sample.players, sample.timestamp));
+    let online_secs: i64 = online.iter().map(|(_, secs)| secs).sum();
+    let average = match online_secs > 0 {
+        true => {
+            online
+                .iter()
+                .map(|(sample, secs)| sample.players as f64 * *secs as f64)
+                .sum::<f64>()
+                / online_secs as f64
+        }
+        false => 0.0,
+    };
+    let full_secs = online
+        .iter()
+        .filter(|(sample, _)| sample.state == ServerState::Full)
+        .map(|(_, secs)| secs)
+        .sum();
+    let queue_secs = online
+        .iter()
+        .filter(|(sample, _)| sample.in_que > 0)
+        .map(|(_, secs)| secs)
+        .sum();
+
+    let mut maps: HashMap<String, i64> = HashMap::new();
+    for (sample, secs) in &online {
+        *maps.entry(sample.map.clone()).or_default() += secs;
+    }
+    let mut maps: Vec<(String, i64)> = maps.into_iter().collect();
+    maps.sort_by_key(|(_, secs)| std::cmp::Reverse(*secs));
+
+    let mut longest_seeding_secs = 0;
+    let mut current = 0;
+    for (sample, secs) in samples.iter().zip(durations.iter()) {
+        match sample.state {
+            ServerState::Seeding => current += secs,
+            _ => current = 0,
+        }
+        longest_seeding_secs = longest_seeding_secs.max(current);
+    }
+
+    Summary {
+        peak,
+        average,
+        full_secs,
+        queue_secs,
+        maps,
+        longest_seeding_secs,
+        uptime: history::uptime(samples),
+    }
+}
+
+pub fn format_duration(secs: i64) -> String {
+    match secs >= 3600 {
+        true => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        false => format!("{}m", secs / 60),
+    }
+}
+
+/// Next time the digest of this period has to be posted.
+fn next_run(period: Period, statics: &message::Static, now: DateTime<Tz>) -> DateTime<Tz> {
+    let mut date = now.date_naive();
+    loop {
+        let run = statics
+            .timezone
+            .from_local_datetime(&date.and_time(statics.digest_time))
+            .earliest();
+        if let Some(run) = run {
+            if run > now && (period == Period::Daily || date.weekday() == statics.digest_weekday) {
+                return run;
+            }
+        }
+        date += Duration::days(1);
+    }
+}
+
+async fn post(ctx: &Context, state: &State, period: Period) -> Result<()> {
+    let statics = state.statics();
+    let end = Utc::now().timestamp();
+    let start = end - period.seconds();
+    let samples = state.history.read().unwrap().since(start).to_vec();
+    let summary = summarize(&samples);
+    let rounds: Vec<i64> = state
+        .rotation
+        .read()
+        .unwrap()
+        .rounds()
+        .filter(|(change, _)| change.timestamp >= start)
+        .map(|(_, length)| length)
+        .collect();
+
+    let graph = graph::encode_png(graph::history_graph(
+        &samples,
+        start,
+        end,
+        statics.timezone,
+        match period {
+            Period::Daily => 3,
+            Period::Weekly => 24,
+        },
+    ))?;
+
+    let peak = match summary.peak {
+        Some((players, timestamp)) => format!(
+            "{} players at {}",
+            players,
+            statics
+                .timezone
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .map(|time| time.format("%a %H:%M").to_string())
+                .unwrap_or_default()
+        ),
+        None => "-".to_string(),
+    };
+    let maps = summary
+        .maps
+        .iter()
+        .take(3)
+        .map(|(map, secs)| format!("{} ({})", map, format_duration(*secs)))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let embed = CreateEmbed::new()
+        .title(match period {
+            Period::Daily => "Daily digest",
+            Period::Weekly => "Weekly digest",
+        })
+        .description(&statics.server_name)
+        .field("Peak", peak, true)
+        .field("Average", format!("{:.1} players", summary.average), true)
+        .field(
+            "Uptime",
+            match summary.uptime {
+                Some(uptime) => format!("{:.1}%", uptime),
+                None => "-".to_string(),
+            },
+            true,
+        )
+        .field("Full", format_duration(summary.full_secs), true)
+        .field("With a queue", format_duration(summary.queue_secs), true)
+        .field(
+            "Longest seeding",
+            format_duration(summary.longest_seeding_secs),
+            true,
+        )
+        .field(
+            "Rounds",
+            match rounds.is_empty() {
+                true => "-".to_string(),
+                false => format!(
+                    "{}, {} on average",
+                    rounds.len(),
+                    format_duration(rounds.iter().sum::<i64>() / rounds.len() as i64)
+                ),
+            },
+            true,
+        )
+        .field(
+            "Most played maps",
+            match maps.is_empty() {
+                true => "-".to_string(),
+                false => maps,
+            },
+            false,
+        )
+        .image("attachment://history.png")
+        .footer(CreateEmbedFooter::new(format!(
+            "times in {}",
+            statics.timezone.name()
+        )));
+
+    ChannelId::new(statics.message_channel)
+        .send_files(
+            &ctx.http,
+            [CreateAttachment::bytes(graph, "history.png")],
+            CreateMessage::new().embed(embed),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Post the configured digests to the alert channel on schedule.
+pub async fn run(ctx: Context, state: Arc<State>) {
+    let periods = match &state.statics().digest[..] {
+        "daily" => vec![Period::Daily],
+        "weekly" => vec![Period::Weekly],
+        "both" => vec![Period::Daily, Period::Weekly],
+        _ => return,
+    };
+    loop {
+        let now = Utc::now().with_timezone(&state.statics().timezone);
+        let (period, run) = periods
+            .iter()
+            .map(|period| (*period, next_run(*period, &state.statics(), now)))
+            .min_by_key(|(_, run)| *run)
+            .unwrap();
+        let wait = (run - now).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        if let Err(e) = post(&ctx, &state, period).await {
+            log::error!("Failed to post {:?} digest: {:#?}", period, e);
+        }
+        // daily and weekly can be due at the same time
+        for other in periods.iter().filter(|other| **other != period) {
+            if next_run(*other, &state.statics(), now) == run {
+                if let Err(e) = post(&ctx, &state, *other).await {
+                    log::error!("Failed to post {:?} digest: {:#?}", other, e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use history::sample;
+
+    #[test]
+    fn summarize_samples() {
+        let samples = vec![
+            sample(0, 10, 0, "Amiens", ServerState::Seeding),
+            sample(1, 15, 0, "Amiens", ServerState::Seeding),
+            sample(2, 40, 0, "Amiens", ServerState::Live),
+            sample(3, 0, 0, "", ServerState::Offline),
+            sample(4, 12, 0, "Verdun", ServerState::Seeding),
+            sample(5, 50, 0, "Verdun", ServerState::Live),
+            sample(6, 64, 3, "Verdun", ServerState::Full),
+            sample(7, 63, 0, "Verdun", ServerState::Full),
+            // the bot wasn't running in between, only 5 minutes count
+            sample(20, 60, 0, "Verdun", ServerState::Live),
+        ];
+        let summary = summarize(&samples);
+        assert_eq!(summary.peak, Some((64, 1_700_000_000 + 6 * 60)));
+        // 6 minutes, the capped 5 minutes and the last minute
+        let total = (10 + 15 + 40 + 12 + 50 + 64) * 60 + 63 * 300 + 60 * 60;
+        assert!((summary.average - total as f64 / 720.0).abs() < 1e-9);
+        assert_eq!(summary.full_secs, 60 + 300);
+        assert_eq!(summary.queue_secs, 60);
+        assert_eq!(
+            summary.maps,
+            vec![("Verdun".to_string(), 540), ("Amiens".to_string(), 180)]
+        );
+        // the offline sample ends the first seeding stretch
+        assert_eq!(summary.longest_seeding_secs, 120);
+        assert!((summary.uptime.unwrap() - 800.0 / 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn summarize_nothing() {
+        let summary = summarize(&[]);
+        assert_eq!(summary.peak, None);
+        assert_eq!(summary.average, 0.0);
+        assert!(summary.maps.is_empty());
+        assert_eq!(summary.uptime, None);
+    }
+
+    #[test]
+    fn summarize_offline() {
+        let samples = vec![
+            sample(0, 0, 0, "", ServerState::Offline),
+            sample(1, 0, 0, "", ServerState::Offline),
+        ];
+        let summary = summarize(&samples);
+        assert_eq!(summary.peak, None);
+        assert_eq!(summary.average, 0.0);
+        assert_eq!(summary.uptime, Some(0.0));
+    }
+
+    #[test]
+    fn durations_in_hours_and_minutes() {
+        assert_eq!(format_duration(59), "0m");
+        assert_eq!(format_duration(45 * 60), "45m");
+        assert_eq!(format_duration(2 * 3600 + 5 * 60), "2h 5m");
+    }
+}
@@ -0,0 +1,44 @@
+use super::{history, rotation, server_info::ServerState};
+use chrono::Utc;
+use serde::Serialize;
+
+/// Everything pushed to the `/events` feed, serialized with a `type` field.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// a new update of the server
+    Sample(history::Sample),
+    /// the server state changed since the previous update
+    Transition {
+        timestamp: i64,
+        from: ServerState,
+        to: ServerState,
+    },
+    /// the server switched to a different map
+    MapChange(rotation::MapChange),
+    /// a message posted in the alert channel
+    Alert {
+        timestamp: i64,
+        title: String,
+        description: String,
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Sample(_) => "sample",
+            Event::Transition { .. } => "transition",
+            Event::MapChange(_) => "map_change",
+            Event::Alert { .. } => "alert",
+        }
+    }
+
+    pub fn alert(title: &str, description: &str) -> Event {
+        Event::Alert {
+            timestamp: Utc::now().timestamp(),
+            title: title.to_string(),
+            description: description.to_string(),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use super::{history::Sample, server_info::ServerState};
+
+/// Seconds of history the forecast looks at.
+const WINDOW: i64 = 20 * 60;
+/// Estimates further out than this are too unreliable to show.
+const MAX_MINUTES: f64 = 6.0 * 60.0;
+
+/// Short-term estimates based on the latest samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Forecast {
+    /// minutes until the server is full, only while it is filling up
+    pub full_in: Option<i64>,
+    /// minutes someone joining the back of the queue now has to wait
+    pub queue_wait: Option<i64>,
+}
+
+/// The last samples without the server going offline or the bot missing
+/// updates in between.
+fn recent(samples: &[Sample]) -> &[Sample] {
+    let last = match samples.last() {
+        Some(last) => last,
+        None => return samples,
+    };
+    let mut start = samples.len() - 1;
+    while start > 0 {
+        let (previous, current) = (&samples[start - 1], &samples[start]);
+        if previous.state == ServerState::Offline
+            || current.timestamp - previous.timestamp > 5 * 60
+            || last.timestamp - previous.timestamp > WINDOW
+        {
+            break;
+        }
+        start -= 1;
+    }
+    &samples[start..]
+}
+
+/// Players gained per minute, fitted over the samples with least squares.
+fn growth(samples: &[Sample]) -> Option<f64> {
+    if samples.len() < 3 {
+        return None;
+    }
+    let first = samples[0].timestamp;
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|sample| {
+            (
+                (sample.timestamp - first) as f64 / 60.0,
+                sample.players as f64,
+            )
+        })
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    match variance > 0.0 {
+        true => Some(covariance / variance),
+        false => None,
+    }
+}
+
+/// Players let in from the queue per minute. The queue only shrinks when
+/// someone gets a slot (or gives up), so the drops between samples are the
+/// churn of the server.
+fn queue_throughput(samples: &[Sample]) -> Option<f64> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let minutes = (last.timestamp - first.timestamp) as f64 / 60.0;
+    if minutes < 5.0 {
+        return None;
+    }
+    let moved: i32 = samples
+        .windows(2)
+        .map(|pair| {
+            let left_queue = (pair[0].in_que - pair[1].in_que).max(0);
+            let gained = (pair[1].players - pair[0].players).max(0);
+            left_queue.max(gained)
+        })
+        .sum();
+    match moved > 0 {
+        true => Some(moved as f64 / minutes),
+        false => None,
+    }
+}
+
+fn minutes(value: f64) -> Option<i64> {
+    match value.is_finite() && value <= MAX_MINUTES {
+        true => Some(value.ceil().max(1.0) as i64),
+        false => None,
+    }
+}
+
+pub fn forecast(samples: &[Sample]) -> Forecast {
+    let samples = recent(samples);
+    let last = match samples.last() {
+        Some(last) if last.state != ServerState::Offline => last,
+        _ => return Forecast::default(),
+    };
+
+    let full_in = match last.state {
+        ServerState::Seeding | ServerState::Live => growth(samples)
+            .filter(|rate| *rate > 0.0)
+            .and_then(|rate| minutes((last.max_players - last.players) as f64 / rate)),
+        _ => None,
+    };
+    let queue_wait = match last.in_que > 0 {
+        true => queue_throughput(samples).and_then(|rate| minutes(last.in_que as f64 / rate)),
+        false => None,
+    };
+    Forecast {
+        full_in,
+        queue_wait,
+    }
+}
+
+impl Forecast {
+    /// Short text like "full in ~12m", none if there is nothing to predict.
+    pub fn describe(&self) -> Option<String> {
+        let parts: Vec<String> = vec![
+            self.full_in.map(|minutes| format!("full in ~{}m", minutes)),
+            self.queue_wait
+                .map(|minutes| format!("~{}m queue", minutes)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        match parts.is_empty() {
+            true => None,
+            false => Some(parts.join(", ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::sample;
+
+    #[test]
+    fn full_in_from_steady_growth() {
+        let samples: Vec<Sample> = (0..5)
+            .map(|minute| {
+                sample(
+                    minute,
+                    40 + minute as i32 * 2,
+                    0,
+                    "Amiens",
+                    ServerState::Live,
+                )
+            })
+            .collect();
+        // 16 slots left at 2 players a minute
+        assert_eq!(forecast(&samples).full_in, Some(8));
+        assert_eq!(forecast(&samples).queue_wait, None);
+    }
+
+    #[test]
+    fn gap_starts_a_new_window() {
+        let mut samples: Vec<Sample> = (0..5)
+            .map(|minute| {
+                sample(
+                    minute,
+                    60 - minute as i32 * 5,
+                    0,
+                    "Amiens",
+                    ServerState::Live,
+                )
+            })
+            .collect();
+        // the bot missed updates, only two samples after the gap
+        samples.push(sample(20, 40, 0, "Amiens", ServerState::Live));
+        samples.push(sample(21, 42, 0, "Amiens", ServerState::Live));
+        assert_eq!(recent(&samples).len(), 2);
+        assert_eq!(forecast(&samples), Forecast::default());
+    }
+
+    #[test]
+    fn offline_sample_starts_a_new_window() {
+        let mut samples = vec![
+            sample(0, 10, 0, "Amiens", ServerState::Seeding),
+            sample(1, 0, 0, "Amiens", ServerState::Offline),
+        ];
+        samples.extend((2..6).map(|minute| {
+            sample(
+                minute,
+                30 + minute as i32,
+                0,
+                "Amiens",
+                ServerState::Seeding,
+            )
+        }));
+        assert_eq!(recent(&samples).len(), 4);
+        assert!(forecast(&samples).full_in.is_some());
+    }
+
+    #[test]
+    fn nothing_while_offline() {
+        let samples = vec![
+            sample(0, 40, 0, "Amiens", ServerState::Live),
+            sample(1, 45, 0, "Amiens", ServerState::Live),
+            sample(2, 50, 0, "Amiens", ServerState::Live),
+            sample(3, 0, 0, "Amiens", ServerState::Offline),
+        ];
+        assert_eq!(forecast(&samples), Forecast::default());
+        assert_eq!(forecast(&[]), Forecast::default());
+    }
+
+    #[test]
+    fn queue_wait_from_throughput() {
+        // a full server letting one player in from the queue every minute
+        let samples: Vec<Sample> = (0..7)
+            .map(|minute| sample(minute, 64, 10 - minute as i32, "Amiens", ServerState::Full))
+            .collect();
+        let forecast = forecast(&samples);
+        assert_eq!(forecast.full_in, None);
+        assert_eq!(forecast.queue_wait, Some(4));
+    }
+
+    #[test]
+    fn queue_wait_needs_a_few_minutes() {
+        let samples: Vec<Sample> = (0..3)
+            .map(|minute| sample(minute, 64, 10 - minute as i32, "Amiens", ServerState::Full))
+            .collect();
+        assert_eq!(forecast(&samples).queue_wait, None);
+    }
+
+    #[test]
+    fn minutes_are_capped() {
+        assert_eq!(minutes(0.2), Some(1));
+        assert_eq!(minutes(12.1), Some(13));
+        assert_eq!(minutes(MAX_MINUTES + 1.0), None);
+        assert_eq!(minutes(f64::INFINITY), None);
+    }
+
+    #[test]
+    fn describe_joins_the_estimates() {
+        assert_eq!(Forecast::default().describe(), None);
+        let forecast = Forecast {
+            full_in: Some(12),
+            queue_wait: Some(5),
+        };
+        assert_eq!(
+            forecast.describe(),
+            Some("full in ~12m, ~5m queue".to_string())
+        );
+    }
+}
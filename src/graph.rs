@@ -0,0 +1,369 @@
+use super::{
+    digest::format_duration,
+    history, players,
+    server_info::ServerState,
+    state::State,
+    text::{FontChain, TextBox},
+};
+use ab_glyph::{FontArc, PxScale};
+use anyhow::Result;
+use chrono::{TimeZone, Timelike};
+use chrono_tz::Tz;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use imageproc::{
+    drawing::{draw_filled_rect_mut, draw_line_segment_mut},
+    rect::Rect,
+};
+use std::io::Cursor;
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 400;
+// space for the labels
+const LEFT: f32 = 60.0;
+const BOTTOM: f32 = 40.0;
+const TOP: f32 = 20.0;
+
+fn font() -> FontChain {
+    FontChain::new(FontArc::try_from_slice(include_bytes!("DejaVuSans.ttf") as &[u8]).unwrap())
+}
+
+pub fn encode_png(image: RgbaImage) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    DynamicImage::ImageRgba8(image).write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Player count of the samples between start and end, with labels in the
+/// given timezone every `label_hours`.
+pub fn history_graph(
+    samples: &[history::Sample],
+    start: i64,
+    end: i64,
+    timezone: Tz,
+    label_hours: i64,
+) -> RgbaImage {
+    let mut graph = RgbaImage::from_pixel(WIDTH, HEIGHT, Rgba([22u8, 24u8, 29u8, 255u8]));
+    let font = font();
+    let grey = Rgba([90u8, 90u8, 90u8, 255u8]);
+    let label_color = Rgba([200u8, 200u8, 200u8, 255u8]);
+    let (plot_width, plot_height) = (WIDTH as f32 - LEFT - 20.0, HEIGHT as f32 - BOTTOM - TOP);
+    let max = samples
+        .iter()
+        .map(|sample| sample.max_players)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let x_of =
+        |timestamp: i64| LEFT + (timestamp - start) as f32 / (end - start) as f32 * plot_width;
+    let y_of = |players: i32| TOP + plot_height - players as f32 / max as f32 * plot_height;
+
+    // horizontal lines at every quarter of the max players
+    for quarter in 0..=4 {
+        let players = max * quarter / 4;
+        let y = y_of(players);
+        draw_line_segment_mut(&mut graph, (LEFT, y), (LEFT + plot_width, y), grey);
+        font.draw(
+            &mut graph,
+            label_color,
+            8,
+            y as i32 - 8,
+            PxScale::from(16.0),
+            &players.to_string(),
+        );
+    }
+
+    // time labels on whole hours in the local timezone
+    let mut label = start - start.rem_euclid(3600) + 3600;
+    while label < end {
+        let local = timezone.timestamp_opt(label, 0).single();
+        if let Some(local) = local.filter(|local| local.hour() as i64 % label_hours.min(24) == 0) {
+            let x = x_of(label);
+            draw_line_segment_mut(
+                &mut graph,
+                (x, TOP),
+                (x, TOP + plot_height),
+                Rgba([45u8, 45u8, 45u8, 255u8]),
+            );
+            let text = match label_hours >= 24 {
+                true => local.format("%a %d").to_string(),
+                false => local.format("%H:%M").to_string(),
+            };
+            font.draw_fitted(
+                &mut graph,
+                label_color,
+                PxScale::from(16.0),
+                &text,
+                TextBox {
+                    x: x as i32 - 24,
+                    y: (TOP + plot_height + 10.0) as i32,
+                    width: 60,
+                    height: 24,
+                },
+                false,
+            );
+        }
+        label += 3600;
+    }
+
+    // player count, with a gap where the server was offline or not watched
+    let mut previous: Option<(i64, f32, f32)> = None;
+    for sample in samples {
+        if sample.state == ServerState::Offline {
+            previous = None;
+            continue;
+        }
+        let point = (x_of(sample.timestamp), y_of(sample.players));
+        if let Some((timestamp, x, y)) = previous {
+            if sample.timestamp - timestamp <= 5 * 60 {
+                for (dx, dy) in [(0.0, -1.0), (0.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (1.0, 0.0)] {
+                    draw_line_segment_mut(
+                        &mut graph,
+                        (x + dx, y + dy),
+                        (point.0 + dx, point.1 + dy),
+                        Rgba([76u8, 204u8, 17u8, 255u8]),
+                    );
+                }
+            }
+        }
+        if sample.in_que > 0 {
+            // mark the moments there was a queue under the graph
+            draw_filled_rect_mut(
+                &mut graph,
+                Rect::at(point.0 as i32, (TOP + plot_height) as i32 - 4).of_size(2, 4),
+                Rgba([0u8, 126u8, 198u8, 255u8]),
+            );
+        }
+        previous = Some((sample.timestamp, point.0, point.1));
+    }
+    graph
+}
+
+const CELL: u32 = 40;
+const HEATMAP_LEFT: u32 = 60;
+const HEATMAP_TOP: u32 = 30;
+
+/// Color of a heatmap cell, from dark for an empty server via yellow to green
+/// for a full one.
+fn heat_color(ratio: f64) -> Rgba<u8> {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let (from, to, part) = match ratio < 0.5 {
+        true => ([40.0, 44.0, 52.0], [223.0, 179.0, 23.0], ratio * 2.0),
+        false => (
+            [223.0, 179.0, 23.0],
+            [76.0, 204.0, 17.0],
+            (ratio - 0.5) * 2.0,
+        ),
+    };
+    let mix = |i: usize| (from[i] + (to[i] - from[i]) * part) as u8;
+    Rgba([mix(0), mix(1), mix(2), 255u8])
+}
+
+/// 7x24 grid of the average players per hour of the week.
+pub fn heatmap(averages: &[[Option<f64>; 24]; 7], max_players: i32, timezone: Tz) -> RgbaImage {
+    let width = HEATMAP_LEFT + 24 * CELL + 10;
+    let height = HEATMAP_TOP + 7 * CELL + 30;
+    let mut map = RgbaImage::from_pixel(width, height, Rgba([22u8, 24u8, 29u8, 255u8]));
+    let font = font();
+    let label_color = Rgba([200u8, 200u8, 200u8, 255u8]);
+
+    for hour in 0..24 {
+        font.draw(
+            &mut map,
+            label_color,
+            (HEATMAP_LEFT + hour * CELL + 8) as i32,
+            6,
+            PxScale::from(16.0),
+            &format!("{:02}", hour),
+        );
+    }
+    for (day, name) in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+        .iter()
+        .enumerate()
+    {
+        let top = HEATMAP_TOP + day as u32 * CELL;
+        font.draw(
+            &mut map,
+            label_color,
+            10,
+            top as i32 + 10,
+            PxScale::from(16.0),
+            name,
+        );
+        for (hour, average) in averages[day].iter().enumerate() {
+            let rect = Rect::at((HEATMAP_LEFT + hour as u32 * CELL) as i32, top as i32)
+                .of_size(CELL - 2, CELL - 2);
+            match average {
+                Some(average) => {
+                    draw_filled_rect_mut(
+                        &mut map,
+                        rect,
+                        heat_color(average / max_players.max(1) as f64),
+                    );
+                    font.draw_fitted(
+                        &mut map,
+                        Rgba([255u8, 255u8, 255u8, 255u8]),
+                        PxScale::from(15.0),
+                        &format!("{:.0}", average),
+                        TextBox {
+                            x: rect.left() + 6,
+                            y: rect.top() + 10,
+                            width: CELL - 12,
+                            height: CELL - 16,
+                        },
+                        false,
+                    );
+                }
+                None => draw_filled_rect_mut(&mut map, rect, Rgba([30u8, 32u8, 38u8, 255u8])),
+            }
+        }
+    }
+    font.draw(
+        &mut map,
+        label_color,
+        HEATMAP_LEFT as i32,
+        (HEATMAP_TOP + 7 * CELL + 6) as i32,
+        PxScale::from(14.0),
+        &format!("average players per hour, {}", timezone.name()),
+    );
+    map
+}
+
+/// Heatmap of the history of the last weeks, or all of it.
+pub fn heatmap_png(state: &State, weeks: Option<i64>) -> Result<Vec<u8>> {
+    let start = match weeks {
+        Some(weeks) => chrono::Utc::now()
+            .timestamp()
+            .saturating_sub(weeks.saturating_mul(7 * 24 * 60 * 60)),
+        None => 0,
+    };
+    // copied so the update loop can add samples while this renders
+    let samples = state.history.read().unwrap().since(start).to_vec();
+    let max_players = samples
+        .iter()
+        .map(|sample| sample.max_players)
+        .max()
+        .unwrap_or(0);
+    let averages = history::hour_of_week(&samples, state.statics().timezone);
+    encode_png(heatmap(&averages, max_players, state.statics().timezone))
+}
+
+const ROW: u32 = 26;
+
+/// All teams next to each other with the squad, rank, name and time on the
+/// server of every player.
+pub fn scoreboard(list: &players::PlayerList, server_name: &str) -> RgbaImage {
+    let column = 480;
+    let rows = list
+        .teams
+        .iter()
+        .map(|team| team.players.len() as u32)
+        .max()
+        .unwrap_or(0);
+    let width = column * list.teams.len().max(1) as u32;
+    let height = 90 + rows * ROW + 10;
+    let mut board = RgbaImage::from_pixel(width, height, Rgba([22u8, 24u8, 29u8, 255u8]));
+    let font = font();
+    let white = Rgba([255u8, 255u8, 255u8, 255u8]);
+    let grey = Rgba([150u8, 150u8, 150u8, 255u8]);
+    let now = chrono::Utc::now().timestamp();
+
+    font.draw_fitted(
+        &mut board,
+        white,
+        PxScale::from(24.0),
+        server_name,
+        TextBox {
+            x: 10,
+            y: 8,
+            width: width - 20,
+            height: 30,
+        },
+        false,
+    );
+    for (i, team) in list.teams.iter().enumerate() {
+        let left = i as u32 * column;
+        let title = match team.name.is_empty() {
+            true => format!("Team {} ({})", i + 1, team.players.len()),
+            false => format!("{} ({})", team.name, team.players.len()),
+        };
+        font.draw_fitted(
+            &mut board,
+            white,
+            PxScale::from(20.0),
+            &title,
+            TextBox {
+                x: left as i32 + 10,
+                y: 44,
+                width: column - 20,
+                height: 24,
+            },
+            false,
+        );
+        for (x, label) in [(10, "squad"), (50, "rank"), (95, "name"), (395, "time")] {
+            font.draw(
+                &mut board,
+                grey,
+                left as i32 + x,
+                68,
+                PxScale::from(13.0),
+                label,
+            );
+        }
+        let mut players: Vec<&players::Player> = team.players.iter().collect();
+        players
+            .sort_by_key(|player| (player.squad.unwrap_or(i64::MAX), player.name.to_lowercase()));
+        for (row, player) in players.iter().enumerate() {
+            let y = (90 + row as u32 * ROW) as i32;
+            if row % 2 == 0 {
+                draw_filled_rect_mut(
+                    &mut board,
+                    Rect::at(left as i32 + 6, y - 3).of_size(column - 12, ROW),
+                    Rgba([30u8, 32u8, 38u8, 255u8]),
+                );
+            }
+            let cells = [
+                (
+                    10,
+                    40,
+                    player
+                        .squad
+                        .map(|squad| squad.to_string())
+                        .unwrap_or_default(),
+                ),
+                (
+                    50,
+                    40,
+                    player.rank.map(|rank| rank.to_string()).unwrap_or_default(),
+                ),
+                (95, 290, player.display_name()),
+                (
+                    395,
+                    75,
+                    player
+                        .time_on_server(now)
+                        .map(format_duration)
+                        .unwrap_or_default(),
+                ),
+            ];
+            for (x, cell_width, text) in cells.iter() {
+                font.draw_fitted(
+                    &mut board,
+                    match *x == 95 {
+                        true => white,
+                        false => grey,
+                    },
+                    PxScale::from(17.0),
+                    text,
+                    TextBox {
+                        x: left as i32 + x,
+                        y,
+                        width: *cell_width,
+                        height: ROW - 4,
+                    },
+                    false,
+                );
+            }
+        }
+    }
+    board
+}
@@ -0,0 +1,239 @@
+use super::{message, store};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Languages the alerts are translated to, as the code and name.
+pub const LANGUAGES: [(&str, &str); 5] = [
+    ("en", "English"),
+    ("de", "Deutsch"),
+    ("fr", "Français"),
+    ("nl", "Nederlands"),
+    ("es", "Español"),
+];
+
+/// The alerts of `message::check`, guilds can turn them off one by one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Alert {
+    LowPlayers,
+    Running,
+    PreRound,
+}
+
+impl Alert {
+    pub const ALL: [Alert; 3] = [Alert::LowPlayers, Alert::Running, Alert::PreRound];
+
+    /// Name used in `/config alert`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alert::LowPlayers => "low_players",
+            Alert::Running => "running",
+            Alert::PreRound => "pre_round",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Alert> {
+        Alert::ALL
+            .iter()
+            .copied()
+            .find(|alert| alert.name() == name)
+    }
+
+    /// Title and first line of the alert in a language, english if it isn't
+    /// translated.
+    pub fn text(&self, lang: &str) -> (&'static str, &'static str) {
+        match (self, lang) {
+            (Alert::LowPlayers, "de") => (
+                "Mir fehlen Spieler! Komm jetzt!",
+                "Perfekter Moment, um ohne Warteschlange beizutreten!",
+            ),
+            (Alert::LowPlayers, "fr") => (
+                "Il me manque des joueurs ! Rejoins-moi !",
+                "Le moment idéal pour rejoindre sans file d'attente !",
+            ),
+            (Alert::LowPlayers, "nl") => (
+                "Ik heb te weinig spelers! Join nu!",
+                "Perfect moment om zonder wachtrij te joinen!",
+            ),
+            (Alert::LowPlayers, "es") => (
+                "¡Me faltan jugadores! ¡Únete ahora!",
+                "¡El momento perfecto para entrar sin cola!",
+            ),
+            (Alert::LowPlayers, _) => (
+                "I'm low on players! Join me now!",
+                "Perfect time to join without queue!",
+            ),
+            (Alert::Running, "de") => ("Ich laufe wieder!", "Alles bestens :slight_smile:"),
+            (Alert::Running, "fr") => ("Je suis lancé !", "Tout va bien :slight_smile:"),
+            (Alert::Running, "nl") => ("Ik draai weer!", "Alles goed :slight_smile:"),
+            (Alert::Running, "es") => ("¡Estoy en marcha!", "Todo bien :slight_smile:"),
+            (Alert::Running, _) => ("I'm up and running!", "Feeling good :slight_smile:"),
+            (Alert::PreRound, "de") => (
+                "Die Vorrunde ist vorbei!",
+                "Kein Warten mehr. Wer jetzt beitritt, kann sofort spielen.",
+            ),
+            (Alert::PreRound, "fr") => (
+                "La pré-manche est terminée !",
+                "Fini d'attendre. Si tu rejoins maintenant, tu joues tout de suite.",
+            ),
+            (Alert::PreRound, "nl") => (
+                "De pre-round is voorbij!",
+                "Niet meer wachten. Als je nu joint kan je meteen spelen.",
+            ),
+            (Alert::PreRound, "es") => (
+                "¡La pre-ronda ha terminado!",
+                "No más esperas. Si entras ahora puedes jugar al instante.",
+            ),
+            (Alert::PreRound, _) => (
+                "Pre-round is over!",
+                "No more waiting. If you join now you can instantly play.",
+            ),
+        }
+    }
+}
+
+/// Settings of a discord server, set with `/config`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildConfig {
+    /// channel for the alerts, none until one is set
+    pub channel: u64,
+    pub seeding_role: u64,
+    pub queue_role: u64,
+    pub lang: String,
+    /// alerts turned off with `/config alert`
+    pub disabled: Vec<Alert>,
+}
+
+impl Default for GuildConfig {
+    fn default() -> Self {
+        GuildConfig {
+            channel: 0,
+            seeding_role: 0,
+            queue_role: 0,
+            lang: "en".to_string(),
+            disabled: Vec::new(),
+        }
+    }
+}
+
+impl GuildConfig {
+    /// Role pinged with the alert, 0 for none.
+    pub fn role(&self, alert: Alert) -> u64 {
+        match alert {
+            Alert::LowPlayers => self.queue_role,
+            Alert::Running | Alert::PreRound => self.seeding_role,
+        }
+    }
+
+    pub fn enabled(&self, alert: Alert) -> bool {
+        !self.disabled.contains(&alert)
+    }
+}
+
+/// Where to post an alert.
+pub struct Target {
+    pub channel: u64,
+    pub role: u64,
+    pub lang: String,
+}
+
+/// Settings per discord server, saved to disk on every change.
+pub struct Guilds {
+    path: String,
+    guilds: HashMap<u64, GuildConfig>,
+}
+
+impl Guilds {
+    pub fn load(path: &str) -> Guilds {
+        Guilds {
+            path: path.to_string(),
+            guilds: store::load(path),
+        }
+    }
+
+    /// Saved settings of the guild, the defaults if it has none. The `guild`
+    /// of the bot starts with the roles of the env settings.
+    fn get(&self, statics: &message::Static, guild: u64) -> GuildConfig {
+        match self.guilds.get(&guild) {
+            Some(config) => config.clone(),
+            None if guild == statics.guild => GuildConfig {
+                seeding_role: statics.seeding_role,
+                queue_role: statics.queue_role,
+                ..GuildConfig::default()
+            },
+            None => GuildConfig::default(),
+        }
+    }
+
+    /// Change the settings of a guild and save them.
+    pub fn update(
+        &mut self,
+        statics: &message::Static,
+        guild: u64,
+        change: impl FnOnce(&mut GuildConfig),
+    ) {
+        let mut config = self.get(statics, guild);
+        change(&mut config);
+        self.guilds.insert(guild, config);
+        store::save(&self.path, &self.guilds);
+    }
+
+    /// Settings of the guild as used by the bot. The env roles are used for
+    /// the `guild` of the bot until they are changed with `/config`, and its
+    /// alerts stay in the env channel until another one is set.
+    pub fn config(&self, statics: &message::Static, guild: u64) -> GuildConfig {
+        let mut config = self.get(statics, guild);
+        if guild == statics.guild && config.channel == 0 {
+            config.channel = statics.message_channel;
+        }
+        config
+    }
+
+    /// Channels to post the alert in.
+    pub fn targets(&self, statics: &message::Static, alert: Alert) -> Vec<Target> {
+        let mut guilds = self.guilds.clone();
+        guilds.insert(statics.guild, self.config(statics, statics.guild));
+        guilds
+            .values()
+            .filter(|config| config.channel != 0 && config.enabled(alert))
+            .map(|config| Target {
+                channel: config.channel,
+                role: config.role(alert),
+                lang: config.lang.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_change_keeps_the_env_roles() {
+        let path = std::env::temp_dir().join(format!("guilds-test-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let statics = message::Static {
+            guild: 1,
+            message_channel: 10,
+            seeding_role: 20,
+            queue_role: 30,
+            ..Default::default()
+        };
+        let mut guilds = Guilds::load(&path);
+        guilds.update(&statics, 1, |config| config.lang = "de".to_string());
+        guilds.update(&statics, 2, |config| config.lang = "fr".to_string());
+        let _ = std::fs::remove_file(&path);
+
+        let home = guilds.config(&statics, 1);
+        assert_eq!((home.seeding_role, home.queue_role), (20, 30));
+        // still follows the env channel
+        assert_eq!(home.channel, 10);
+        assert_eq!(guilds.guilds[&1].channel, 0);
+        let other = guilds.config(&statics, 2);
+        assert_eq!(
+            (other.channel, other.seeding_role, other.queue_role),
+            (0, 0, 0)
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use super::{message, server_info};
+use anyhow::Result;
+use chrono::{Datelike, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+/// A single update of the server, stored one json object per line.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Sample {
+    /// unix timestamp in seconds
+    pub timestamp: i64,
+    pub players: i32,
+    pub max_players: i32,
+    pub in_que: i32,
+    pub map: String,
+    pub mode: String,
+    pub state: server_info::ServerState,
+}
+
+impl Sample {
+    pub fn new(status: &server_info::ServerInfo, statics: &message::Static) -> Sample {
+        Sample {
+            timestamp: Utc::now().timestamp(),
+            players: status.detailed.current_players,
+            max_players: status.detailed.max_players,
+            in_que: status.detailed.in_que.unwrap_or(0),
+            map: status.detailed.server_map.clone(),
+            mode: status.detailed.map_mode.clone(),
+            state: server_info::ServerState::from_status(status, statics),
+        }
+    }
+
+    /// Sample for an update where the server couldn't be found.
+    pub fn offline() -> Sample {
+        Sample {
+            timestamp: Utc::now().timestamp(),
+            players: 0,
+            max_players: 0,
+            in_que: 0,
+            map: "".to_string(),
+            mode: "".to_string(),
+            state: server_info::ServerState::Offline,
+        }
+    }
+}
+
+/// Sample `minute` minutes into a fixed test day on a 64 player server.
+#[cfg(test)]
+pub fn sample(
+    minute: i64,
+    players: i32,
+    in_que: i32,
+    map: &str,
+    state: server_info::ServerState,
+) -> Sample {
+    Sample {
+        timestamp: 1_700_000_000 + minute * 60,
+        players,
+        max_players: 64,
+        in_que,
+        map: map.to_string(),
+        mode: "Conquest".to_string(),
+        state,
+    }
+}
+
+/// Player counts of the last `history_days`, kept in memory and appended to
+/// the history file so it survives restarts.
+pub struct History {
+    path: String,
+    retention: i64,
+    samples: Vec<Sample>,
+}
+
+impl History {
+    pub fn load(path: &str, retention_days: i64) -> History {
+        let samples = match fs::read_to_string(path) {
+            Ok(content) => content
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Sample>(line).ok())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        let mut history = History {
+            path: path.to_string(),
+            retention: retention_days * 24 * 60 * 60,
+            samples,
+        };
+        if let Err(e) = history.compact() {
+            log::error!("Failed to rewrite history file: {:#?}", e);
+        }
+        log::info!("Loaded {} history samples", history.samples.len());
+        history
+    }
+
+    /// Remove samples older than the retention and rewrite the file.
+    fn compact(&mut self) -> Result<()> {
+        let oldest = Utc::now().timestamp() - self.retention;
+        self.samples.retain(|sample| sample.timestamp >= oldest);
+        let mut content = String::new();
+        for sample in &self.samples {
+            content.push_str(&serde_json::to_string(sample)?);
+            content.push('\n');
+        }
+        fs::write(&self.path, content)?;
+        Ok(())
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        let line = serde_json::to_string(&sample).unwrap_or_default();
+        self.samples.push(sample);
+
+        // only rewrite the whole file once a day worth of samples is expired
+        let expired = self
+            .samples
+            .first()
+            .map(|first| first.timestamp < Utc::now().timestamp() - self.retention - 24 * 60 * 60)
+            .unwrap_or(false);
+        let result = match expired {
+            true => self.compact(),
+            false => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .and_then(|mut file| writeln!(file, "{}", line))
+                .map_err(anyhow::Error::from),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to save history sample: {:#?}", e);
+        }
+    }
+
+    pub fn last(&self) -> Option<&Sample> {
+        self.samples.last()
+    }
+
+    /// All samples since the given unix timestamp.
+    pub fn since(&self, timestamp: i64) -> &[Sample] {
+        let start = self
+            .samples
+            .partition_point(|sample| sample.timestamp < timestamp);
+        &self.samples[start..]
+    }
+
+    /// Player counts of the last hours averaged down to at most `points`
+    /// values, skipping the moments the server was offline.
+    pub fn recent_players(&self, hours: i64, points: usize) -> Vec<i32> {
+        let online: Vec<i32> = self
+            .since(Utc::now().timestamp() - hours * 60 * 60)
+            .iter()
+            .filter(|sample| sample.state != server_info::ServerState::Offline)
+            .map(|sample| sample.players)
+            .collect();
+        let chunk = online.len().div_ceil(points.max(1)).max(1);
+        online
+            .chunks(chunk)
+            .map(|chunk| chunk.iter().sum::<i32>() / chunk.len() as i32)
+            .collect()
+    }
+}
+
+/// Average players per hour of the week (monday first) in the given timezone,
+/// none for hours without any samples.
+pub fn hour_of_week(samples: &[Sample], timezone: Tz) -> [[Option<f64>; 24]; 7] {
+    let mut totals = [[(0i64, 0i64); 24]; 7];
+    for sample in samples {
+        if sample.state == server_info::ServerState::Offline {
+            continue;
+        }
+        if let Some(local) = timezone.timestamp_opt(sample.timestamp, 0).single() {
+            let total =
+                &mut totals[local.weekday().num_days_from_monday() as usize][local.hour() as usize];
+            total.0 += sample.players as i64;
+            total.1 += 1;
+        }
+    }
+    totals.map(|day| {
+        day.map(|(players, count)| match count > 0 {
+            true => Some(players as f64 / count as f64),
+            false => None,
+        })
+    })
+}
+
+/// Percentage of samples the server could be found in.
+pub fn uptime(samples: &[Sample]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let online = samples
+        .iter()
+        .filter(|sample| sample.state != server_info::ServerState::Offline)
+        .count();
+    Some(online as f64 / samples.len() as f64 * 100.0)
+}
@@ -1,8 +1,10 @@
 use anyhow::Result;
 use chrono::Utc;
 use serenity::{
+    all::Interaction,
     builder::{CreateAttachment, EditProfile},
     client::{Client, Context, EventHandler},
+    gateway::ActivityData,
     model::gateway::Ready,
     prelude::GatewayIntents,
 };
@@ -11,11 +13,38 @@ use std::{
     sync::{atomic, Arc},
     {env, time},
 };
-use warp::Filter;
+mod accounts;
+mod admins;
+mod commands;
+mod digest;
+mod events;
+mod forecast;
+mod graph;
+mod guilds;
+mod history;
+mod members;
 mod message;
+mod notify;
+mod players;
+mod playtime;
+mod render;
+mod roles;
+mod rotation;
+mod seeding;
 mod server_info;
+mod settings;
+mod state;
+mod stats;
+mod store;
+mod text;
+mod watch;
+mod web;
 
-struct Handler;
+struct Handler {
+    state: Arc<state::State>,
+    // ready gets called again on reconnects, only start the loops once
+    started: atomic::AtomicBool,
+}
 
 #[serenity::async_trait]
 impl EventHandler for Handler {
@@ -23,9 +52,13 @@ impl EventHandler for Handler {
         let user = ctx.cache.current_user().clone();
         log::info!("Logged in as {:#?}", user.name);
 
-        let last_update = Arc::new(atomic::AtomicI64::new(0));
-        let last_update_clone = Arc::clone(&last_update);
+        commands::register(&ctx, &self.state).await;
+        if self.started.swap(true, atomic::Ordering::Relaxed) {
+            return;
+        }
 
+        let state = Arc::clone(&self.state);
+        let statics = state.statics();
         let mut message_globals = message::Global {
             game_id: String::from(""),
             since_empty: false,
@@ -33,61 +66,10 @@ impl EventHandler for Handler {
             since_player_trigger: 5,
         };
 
-        let statics = message::Static {
-            server_name: env::var("name").expect("name wasn't given an argument!"),
-            // optional:
-            server_id: env::var("guid").unwrap_or_else(|_| "none".to_string()),
-            game: env::var("game").unwrap_or_else(|_| "tunguska".to_string()),
-            owner_id: env::var("ownerId").unwrap_or_else(|_| "none".to_string()),
-            platform: env::var("platform").unwrap_or_else(|_| "pc".to_string()),
-            fake_players: env::var("fakeplayers").unwrap_or_else(|_| "no".to_string()),
-            set_banner_image: env::var("serverbanner").unwrap_or_else(|_| "yes".to_string()),
-            lang: env::var("lang")
-                .unwrap_or_else(|_| "en-us".to_string())
-                .to_lowercase(),
-            mins_between_avatar_change: env::var("mins_between_avatar_change")
-                .unwrap_or_else(|_| "1".to_string())
-                .parse::<i32>()
-                .expect("mins_between_avatar_change wasn't given an integer!"),
-            started_amount: env::var("startedamount")
-                .unwrap_or_else(|_| "50".to_string())
-                .parse::<i32>()
-                .expect("startedamount wasn't given an integer!"),
-            message_channel: env::var("channel")
-                .unwrap_or_else(|_| "default_channel_value".to_string())
-                .parse::<u64>()
-                .expect("channel wasn't given an integer!"),
-            min_player_amount: env::var("minplayeramount")
-                .unwrap_or_else(|_| "20".to_string())
-                .parse::<i32>()
-                .expect("I wasn't given an integer!"),
-            amount_of_prev_request: env::var("prevrequestcount")
-                .unwrap_or_else(|_| "5".to_string())
-                .parse::<i32>()
-                .expect("prevrequestcount wasn't given an integer!"),
-            include_spectators: env::var("include_spectators").unwrap_or_else(|_| "no".to_string()),
-        };
-
         log::info!("Started monitoring server {:#?}", statics.server_name);
 
-        tokio::spawn(async move {
-            let hello = warp::any().map(move || {
-                let last_update_i64 = last_update_clone.load(atomic::Ordering::Relaxed);
-                let now_minutes = Utc::now().timestamp() / 60;
-                if (now_minutes - last_update_i64) > 5 {
-                    warp::reply::with_status(
-                        format!("{}", now_minutes - last_update_i64),
-                        warp::http::StatusCode::SERVICE_UNAVAILABLE,
-                    )
-                } else {
-                    warp::reply::with_status(
-                        format!("{}", now_minutes - last_update_i64),
-                        warp::http::StatusCode::OK,
-                    )
-                }
-            });
-            warp::serve(hello).run(([0, 0, 0, 0], 3030)).await;
-        });
+        tokio::spawn(web::serve(Arc::clone(&state)));
+        tokio::spawn(digest::run(ctx.clone(), Arc::clone(&state)));
 
         // loop in separate async
         tokio::spawn(async move {
@@ -96,11 +78,13 @@ impl EventHandler for Handler {
                 - chrono::Duration::minutes(statics.mins_between_avatar_change.into());
             loop {
                 let old_message_globals = message_globals.clone();
+                // settings can be changed with `/config set` in between
                 message_globals = match status(
                     ctx.clone(),
                     message_globals,
-                    statics.clone(),
+                    state.statics(),
                     update_avatar,
+                    &state,
                 )
                 .await
                 {
@@ -114,12 +98,30 @@ impl EventHandler for Handler {
                         old_message_globals.clone()
                     }
                 };
-                last_update.store(Utc::now().timestamp() / 60, atomic::Ordering::Relaxed);
+                state
+                    .last_update
+                    .store(Utc::now().timestamp() / 60, atomic::Ordering::Relaxed);
                 // wait 2 minutes before redo
                 tokio::time::sleep(time::Duration::from_secs(60)).await;
             }
         });
     }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::Command(command) => commands::handle(&ctx, &self.state, &command).await,
+            Interaction::Autocomplete(command) => {
+                commands::autocomplete(&ctx, &self.state, &command).await
+            }
+            Interaction::Component(component) => match component.data.custom_id.split(':').next() {
+                Some("role") => roles::handle(&ctx, &self.state, &component).await,
+                Some("seed") => seeding::handle(&ctx, &self.state, &component).await,
+                Some("players") => players::handle(&ctx, &self.state, &component).await,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
 }
 
 async fn status(
@@ -127,16 +129,77 @@ async fn status(
     message_globals: message::Global,
     statics: message::Static,
     mut update_avatar: chrono::DateTime<Utc>,
+    state: &Arc<state::State>,
 ) -> Result<(message::Global, chrono::DateTime<Utc>)> {
-    let status =
-        server_info::change_name(ctx.clone(), statics.clone(), &message_globals.game_id).await?;
-    let image_loc = server_info::gen_img(status.clone(), statics.clone()).await?;
-
+    let status = match server_info::change_name(
+        ctx.clone(),
+        statics.clone(),
+        &message_globals.game_id,
+    )
+    .await
+    {
+        Ok(status) => status,
+        Err(e) => {
+            *state.status.write().unwrap() = None;
+            add_sample(state, history::Sample::offline());
+            return Err(e);
+        }
+    };
+    add_sample(state, history::Sample::new(&status, &statics));
+    notify::check(&ctx, state, &status).await;
+    seeding::update(&ctx, state, &status).await;
+    if players::supported(&statics) {
+        match players::fetch(&statics, &status).await {
+            Ok(list) => {
+                let previous = state.players.write().unwrap().replace(list.clone());
+                if let Some(previous) = previous {
+                    watch::check(&ctx, state, &previous, &list).await;
+                }
+                state.playtime.write().unwrap().update(&list);
+                admins::update(&ctx, state, &status, &list).await;
+                // these can take a while, don't hold up the avatar and alerts
+                // for them. Skipped while the ones of an earlier update run.
+                let mut lookups = state.lookups.lock().unwrap();
+                if lookups
+                    .as_ref()
+                    .map(|task| task.is_finished())
+                    .unwrap_or(true)
+                {
+                    let (ctx, state) = (ctx.clone(), Arc::clone(state));
+                    *lookups = Some(tokio::spawn(async move {
+                        stats::check(&ctx, &state, &list).await;
+                        members::update_role(&ctx, &state, &list).await;
+                    }));
+                }
+            }
+            Err(e) => log::error!("Failed to get the player list: {:#?}", e),
+        }
+    }
+    let mut extra = Vec::new();
+    if &statics.forecast_presence[..] == "yes" {
+        extra.extend(state.forecast().describe());
+    }
+    extra.extend(admins::describe(state));
+    if !extra.is_empty() {
+        ctx.set_activity(Some(ActivityData::playing(format!(
+            "{} | {}",
+            server_info::activity_text(&status, &statics),
+            extra.join(" | ")
+        ))));
+    }
+    let samples = state.history.read().unwrap().recent_players(3, 60);
     // only allow updating once a minute to avoid spamming the avatar api
-    if update_avatar.add(chrono::Duration::minutes(
+    let avatar_due = update_avatar.add(chrono::Duration::minutes(
         statics.mins_between_avatar_change.into(),
-    )) <= chrono::Utc::now()
-    {
+    )) <= chrono::Utc::now();
+    let (image_loc, map) =
+        server_info::gen_img(status.clone(), statics.clone(), &samples, avatar_due).await?;
+    // keep the last status around, it can be used to preview images with `render --info`
+    std::fs::write("./server_info.json", serde_json::to_string_pretty(&status)?)?;
+    *state.status.write().unwrap() = Some(status.clone());
+    *state.map_image.write().unwrap() = Some(map);
+
+    if avatar_due {
         // change avatar
         let avatar = CreateAttachment::path(image_loc)
             .await
@@ -145,7 +208,7 @@ async fn status(
 
         let mut new_profile = EditProfile::new().avatar(&avatar);
         if &statics.set_banner_image[..] == "yes" {
-            let banner = CreateAttachment::path("./map.jpg")
+            let banner = CreateAttachment::path("./banner.png")
                 .await
                 .expect("Failed to read banner image");
             new_profile = new_profile.banner(&banner);
@@ -163,11 +226,172 @@ async fn status(
     }
 
     Ok((
-        message::check(ctx, status.clone(), message_globals, statics).await?,
+        message::check(ctx, status.clone(), message_globals, statics, state).await?,
         update_avatar,
     ))
 }
 
+fn statics_from_env() -> message::Static {
+    message::Static {
+        server_name: env::var("name").expect("name wasn't given an argument!"),
+        // optional:
+        server_id: env::var("guid").unwrap_or_else(|_| "none".to_string()),
+        game: env::var("game").unwrap_or_else(|_| "tunguska".to_string()),
+        owner_id: env::var("ownerId").unwrap_or_else(|_| "none".to_string()),
+        platform: env::var("platform").unwrap_or_else(|_| "pc".to_string()),
+        fake_players: env::var("fakeplayers").unwrap_or_else(|_| "no".to_string()),
+        set_banner_image: env::var("serverbanner").unwrap_or_else(|_| "yes".to_string()),
+        lang: env::var("lang")
+            .unwrap_or_else(|_| "en-us".to_string())
+            .to_lowercase(),
+        mins_between_avatar_change: env::var("mins_between_avatar_change")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<i32>()
+            .expect("mins_between_avatar_change wasn't given an integer!"),
+        started_amount: env::var("startedamount")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse::<i32>()
+            .expect("startedamount wasn't given an integer!"),
+        message_channel: env::var("channel")
+            .unwrap_or_else(|_| "default_channel_value".to_string())
+            .parse::<u64>()
+            .expect("channel wasn't given an integer!"),
+        min_player_amount: env::var("minplayeramount")
+            .unwrap_or_else(|_| "20".to_string())
+            .parse::<i32>()
+            .expect("I wasn't given an integer!"),
+        amount_of_prev_request: env::var("prevrequestcount")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<i32>()
+            .expect("prevrequestcount wasn't given an integer!"),
+        include_spectators: env::var("include_spectators").unwrap_or_else(|_| "no".to_string()),
+        animated_avatar: env::var("animated_avatar").unwrap_or_else(|_| "no".to_string()),
+        history_file: env::var("history_file").unwrap_or_else(|_| "./history.jsonl".to_string()),
+        watchlist_file: env::var("watchlist_file")
+            .unwrap_or_else(|_| "./watchlist.json".to_string()),
+        watch_channel: env::var("watch_channel")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()
+            .expect("watch_channel wasn't given an integer!"),
+        admins: env::var("admins")
+            .unwrap_or_default()
+            .split(',')
+            .map(|admin| admin.trim().to_string())
+            .filter(|admin| !admin.is_empty())
+            .collect(),
+        admin_absence_mins: env::var("admin_absence_mins")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<i64>()
+            .expect("admin_absence_mins wasn't given an integer!"),
+        admin_channel: env::var("admin_channel")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()
+            .expect("admin_channel wasn't given an integer!"),
+        max_kpm: env::var("max_kpm")
+            .unwrap_or_else(|_| "2.0".to_string())
+            .parse::<f64>()
+            .expect("max_kpm wasn't given a number!"),
+        max_accuracy: env::var("max_accuracy")
+            .unwrap_or_else(|_| "35".to_string())
+            .parse::<f64>()
+            .expect("max_accuracy wasn't given a number!"),
+        max_kd: env::var("max_kd")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse::<f64>()
+            .expect("max_kd wasn't given a number!"),
+        new_account_hours: env::var("new_account_hours")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<i64>()
+            .expect("new_account_hours wasn't given an integer!"),
+        playtime_file: env::var("playtime_file").unwrap_or_else(|_| "./playtime.json".to_string()),
+        settings_file: env::var("settings_file").unwrap_or_else(|_| "./settings.json".to_string()),
+        guilds_file: env::var("guilds_file").unwrap_or_else(|_| "./guilds.json".to_string()),
+        accounts_file: env::var("accounts_file").unwrap_or_else(|_| "./accounts.json".to_string()),
+        subscriptions_file: env::var("subscriptions_file")
+            .unwrap_or_else(|_| "./subscriptions.json".to_string()),
+        rotation_file: env::var("rotation_file").unwrap_or_else(|_| "./rotation.jsonl".to_string()),
+        history_days: env::var("history_days")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<i64>()
+            .expect("history_days wasn't given an integer!"),
+        status_page: env::var("status_page").unwrap_or_else(|_| "no".to_string()),
+        digest: env::var("digest").unwrap_or_else(|_| "no".to_string()),
+        digest_time: chrono::NaiveTime::parse_from_str(
+            &env::var("digest_time").unwrap_or_else(|_| "09:00".to_string()),
+            "%H:%M",
+        )
+        .expect("digest_time wasn't given a time like 09:00!"),
+        digest_weekday: env::var("digest_weekday")
+            .unwrap_or_else(|_| "mon".to_string())
+            .parse::<chrono::Weekday>()
+            .expect("digest_weekday wasn't given a weekday!"),
+        timezone: env::var("timezone")
+            .unwrap_or_else(|_| "UTC".to_string())
+            .parse::<chrono_tz::Tz>()
+            .expect("timezone wasn't given a timezone like Europe/Amsterdam!"),
+        forecast_presence: env::var("forecast_presence").unwrap_or_else(|_| "no".to_string()),
+        // the docker image sets a placeholder, that means no guild
+        guild: env::var("guild")
+            .ok()
+            .and_then(|guild| guild.parse::<u64>().ok())
+            .unwrap_or(0),
+        seeding_hours: env::var("seeding_hours").ok().map(|hours| {
+            hours
+                .split_once('-')
+                .and_then(|(start, end)| {
+                    Some((
+                        start.trim().parse::<u32>().ok()?,
+                        end.trim().parse::<u32>().ok()?,
+                    ))
+                })
+                // an empty range would never match
+                .filter(|(start, end)| *start < 24 && *end < 24 && start != end)
+                .expect("seeding_hours wasn't given two different hours from 0 to 23 like 17-23!")
+        }),
+        seeding_cooldown: env::var("seeding_cooldown")
+            .unwrap_or_else(|_| "120".to_string())
+            .parse::<i64>()
+            .expect("seeding_cooldown wasn't given an integer!"),
+        seeding_role: env::var("seeding_role")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()
+            .expect("seeding_role wasn't given an integer!"),
+        playing_role: env::var("playing_role")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()
+            .expect("playing_role wasn't given an integer!"),
+        queue_role: env::var("queue_role")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()
+            .expect("queue_role wasn't given an integer!"),
+    }
+}
+
+/// Save a sample in the history and push it (and the state or map change if
+/// there is one) to the live feed.
+fn add_sample(state: &state::State, sample: history::Sample) {
+    let mut history = state.history.write().unwrap();
+    if let Some(previous) = history.last() {
+        if previous.state != sample.state {
+            state.publish(events::Event::Transition {
+                timestamp: sample.timestamp,
+                from: previous.state,
+                to: sample.state,
+            });
+        }
+    }
+    let change = state
+        .rotation
+        .write()
+        .unwrap()
+        .update(&sample, history.last());
+    if let Some(change) = change {
+        state.publish(events::Event::MapChange(change));
+    }
+    state.publish(events::Event::Sample(sample.clone()));
+    history.push(sample);
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     log::set_max_level(log::LevelFilter::Info);
@@ -175,11 +399,20 @@ async fn main() -> anyhow::Result<()> {
         .unwrap_or_else(|e| panic!("Logger initialization failed with {:#?}", e))
         .start()?;
 
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("render") {
+        return render::run(&args[2..]).await;
+    }
+
     // Login with a bot token from the environment
     let token = &env::var("token").expect("token wasn't given an argument!")[..];
+    let handler = Handler {
+        state: Arc::new(state::State::new(statics_from_env())),
+        started: atomic::AtomicBool::new(false),
+    };
     let intents = GatewayIntents::non_privileged();
     let mut client = Client::builder(token, intents)
-        .event_handler(Handler)
+        .event_handler(handler)
         .await
         .expect("Error creating client");
 
@@ -0,0 +1,110 @@
+use super::{players::PlayerList, server_info::api_game, state::State};
+use serenity::{
+    client::Context,
+    model::id::{GuildId, RoleId, UserId},
+};
+use std::collections::HashSet;
+
+/// Discord users with a linked account that is on the server.
+pub fn playing(state: &State, list: &PlayerList) -> Vec<u64> {
+    let statics = state.statics();
+    let linked = state
+        .accounts
+        .read()
+        .unwrap()
+        .linked(api_game(&statics.game), &statics.platform);
+    let mut users: Vec<u64> = list
+        .all()
+        .filter_map(|player| {
+            linked
+                .iter()
+                .find(|(_, account)| match player.player_id {
+                    Some(id) => id == account.persona_id,
+                    None => player.name.eq_ignore_ascii_case(&account.name),
+                })
+                .map(|(user, _)| *user)
+        })
+        .collect();
+    users.sort_unstable();
+    users.dedup();
+    users
+}
+
+/// Take a left over playing role from the linked members that aren't playing
+/// after a restart. There can be a lot of them, so it runs on its own.
+fn sweep(ctx: &Context, guild: GuildId, role: RoleId, users: Vec<u64>) {
+    let http = ctx.http.clone();
+    tokio::spawn(async move {
+        for user in users {
+            if let Err(e) = http
+                .remove_member_role(guild, UserId::new(user), role, Some("Not on the server"))
+                .await
+            {
+                log::error!("Failed to take the playing role from {}: {:#?}", user, e);
+            }
+        }
+    });
+}
+
+/// Give the playing role to the members that joined the server and take it
+/// from the ones that left. Members whose role couldn't be changed are tried
+/// again on the next update.
+pub async fn update_role(ctx: &Context, state: &State, list: &PlayerList) {
+    let statics = state.statics();
+    if statics.playing_role == 0 || statics.guild == 0 {
+        return;
+    }
+    let guild = GuildId::new(statics.guild);
+    let role = RoleId::new(statics.playing_role);
+    let current: HashSet<u64> = playing(state, list).into_iter().collect();
+    let previous = state.playing.lock().unwrap().clone();
+    let mut playing = match previous {
+        Some(previous) => previous,
+        // first list since a restart: take the role from everyone with a
+        // linked account that isn't playing
+        None => {
+            let linked: HashSet<u64> = state
+                .accounts
+                .read()
+                .unwrap()
+                .linked(api_game(&statics.game), &statics.platform)
+                .into_iter()
+                .map(|(user, _)| user)
+                .collect();
+            sweep(
+                ctx,
+                guild,
+                role,
+                linked.difference(&current).copied().collect(),
+            );
+            HashSet::new()
+        }
+    };
+    let joined: Vec<u64> = current.difference(&playing).copied().collect();
+    let left: Vec<u64> = playing.difference(&current).copied().collect();
+    for user in joined {
+        match ctx
+            .http
+            .add_member_role(guild, UserId::new(user), role, Some("Joined the server"))
+            .await
+        {
+            Ok(()) => {
+                playing.insert(user);
+            }
+            Err(e) => log::error!("Failed to give the playing role to {}: {:#?}", user, e),
+        }
+    }
+    for user in left {
+        match ctx
+            .http
+            .remove_member_role(guild, UserId::new(user), role, Some("Left the server"))
+            .await
+        {
+            Ok(()) => {
+                playing.remove(&user);
+            }
+            Err(e) => log::error!("Failed to take the playing role from {}: {:#?}", user, e),
+        }
+    }
+    *state.playing.lock().unwrap() = Some(playing);
+}
@@ -1,9 +1,11 @@
-use super::server_info;
+use super::{events, guilds::Alert, server_info, state};
 use anyhow::Result;
 use serenity::{
-    builder::{CreateAttachment, CreateEmbed, CreateEmbedFooter, CreateMessage},
+    builder::{
+        CreateAllowedMentions, CreateAttachment, CreateEmbed, CreateEmbedFooter, CreateMessage,
+    },
     client::Context,
-    model::id::ChannelId,
+    model::id::{ChannelId, RoleId},
 };
 
 #[derive(Clone, Debug)]
@@ -30,6 +32,90 @@ pub struct Static {
     pub started_amount: i32,
     pub mins_between_avatar_change: i32,
     pub include_spectators: String,
+    pub animated_avatar: String,
+    pub history_file: String,
+    pub rotation_file: String,
+    pub subscriptions_file: String,
+    pub accounts_file: String,
+    pub guilds_file: String,
+    pub settings_file: String,
+    pub playtime_file: String,
+    pub admin_channel: u64,
+    /// in-game names or persona ids of the admins
+    pub admins: Vec<String>,
+    pub admin_absence_mins: i64,
+    pub max_kpm: f64,
+    pub max_accuracy: f64,
+    pub max_kd: f64,
+    pub new_account_hours: i64,
+    pub watchlist_file: String,
+    pub watch_channel: u64,
+    pub history_days: i64,
+    pub status_page: String,
+    pub digest: String,
+    pub digest_time: chrono::NaiveTime,
+    pub digest_weekday: chrono::Weekday,
+    pub timezone: chrono_tz::Tz,
+    pub forecast_presence: String,
+    pub guild: u64,
+    pub seeding_hours: Option<(u32, u32)>,
+    pub seeding_cooldown: i64,
+    pub seeding_role: u64,
+    pub queue_role: u64,
+    pub playing_role: u64,
+}
+
+impl Default for Static {
+    /// Same defaults as the optional env variables, used when rendering
+    /// images without running the bot.
+    fn default() -> Self {
+        Static {
+            server_id: "none".to_string(),
+            game: "tunguska".to_string(),
+            platform: "pc".to_string(),
+            owner_id: "none".to_string(),
+            fake_players: "no".to_string(),
+            set_banner_image: "yes".to_string(),
+            server_name: "".to_string(),
+            lang: "en-us".to_string(),
+            min_player_amount: 20,
+            amount_of_prev_request: 5,
+            message_channel: 0,
+            started_amount: 50,
+            mins_between_avatar_change: 1,
+            include_spectators: "no".to_string(),
+            animated_avatar: "no".to_string(),
+            history_file: "./history.jsonl".to_string(),
+            rotation_file: "./rotation.jsonl".to_string(),
+            subscriptions_file: "./subscriptions.json".to_string(),
+            accounts_file: "./accounts.json".to_string(),
+            guilds_file: "./guilds.json".to_string(),
+            settings_file: "./settings.json".to_string(),
+            playtime_file: "./playtime.json".to_string(),
+            admin_channel: 0,
+            admins: Vec::new(),
+            admin_absence_mins: 30,
+            max_kpm: 2.0,
+            max_accuracy: 35.0,
+            max_kd: 4.0,
+            new_account_hours: 10,
+            watchlist_file: "./watchlist.json".to_string(),
+            watch_channel: 0,
+            history_days: 30,
+            status_page: "no".to_string(),
+            digest: "no".to_string(),
+            digest_time: chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            digest_weekday: chrono::Weekday::Mon,
+            timezone: chrono_tz::UTC,
+            forecast_presence: "no".to_string(),
+            guild: 0,
+            seeding_hours: None,
+            seeding_cooldown: 120,
+            seeding_role: 0,
+            queue_role: 0,
+            playing_role: 0,
+        }
+    }
 }
 
 pub async fn check(
@@ -37,6 +123,7 @@ pub async fn check(
     status: server_info::ServerInfo,
     mut globals: Global,
     statics: Static,
+    state: &state::State,
 ) -> Result<Global> {
     if statics.message_channel != 40 {
         // in_spectator
@@ -60,6 +147,14 @@ pub async fn check(
             image_url = "only_favorites_image.jpg";
         }
 
+        // prev_request_count can be lowered with `/config set`, shrink to the
+        // new window at once
+        let window = (statics.amount_of_prev_request * 2).max(0) as usize;
+        let len = globals.previous_request.len();
+        if len > window {
+            globals.previous_request.drain(..len - window);
+        }
+
         let mut test = false;
         for request in globals.clone().previous_request.iter() {
             if request - status.detailed.current_players >= statics.min_player_amount
@@ -76,11 +171,11 @@ pub async fn check(
             // if prev test true, send message
             send(
                 ctx.clone(),
-                statics.clone(),
+                state,
                 image_url,
                 status.clone(),
-                "I'm low on players! Join me now!",
-                &format!("Perfect time to join without queue!\n{}", server_info)[..],
+                Alert::LowPlayers,
+                &server_info,
             )
             .await?;
         } else {
@@ -98,11 +193,11 @@ pub async fn check(
             if status.detailed.server_name.contains("AMG") {
                 send(
                     ctx.clone(),
-                    statics.clone(),
+                    state,
                     image_url,
                     status.clone(),
-                    "I'm up and running!",
-                    &format!("Feeling good :slight_smile:\n{}", server_info)[..],
+                    Alert::Running,
+                    &server_info,
                 )
                 .await?;
             }
@@ -117,14 +212,11 @@ pub async fn check(
                 // # if the past messages are below 20
                 send(
                     ctx.clone(),
-                    statics.clone(),
+                    state,
                     image_url,
                     status.clone(),
-                    "Pre-round is over!",
-                    &format!(
-                        "No more waiting. If you join now you can instantly play.\n{}",
-                        server_info
-                    )[..],
+                    Alert::PreRound,
+                    &server_info,
                 )
                 .await?;
             }
@@ -143,45 +235,78 @@ pub async fn check(
     Ok(globals)
 }
 
-pub async fn send(
-    ctx: Context,
-    statics: Static,
-    image_url: &str,
-    status: server_info::ServerInfo,
-    title: &str,
-    description: &str,
-) -> Result<serenity::model::channel::Message, serenity::Error> {
-    let paths = CreateAttachment::path(image_url).await?;
-    let games = std::collections::HashMap::from([
-        ("tunguska", "bf1"),
-        ("casablanca", "bfv"),
-        ("kingston", "bf2042"),
-    ]);
+/// Link to the server on gametools.network
+pub fn server_link(statics: &Static, game_id: &str) -> String {
     let mut gather_type = "gameid";
     if statics.game == "kingston" {
         gather_type = "serverid";
-    } else if status.game_id.clone().unwrap_or_default().contains(':') {
+    } else if game_id.contains(':') {
         gather_type = "serverip";
     }
-    let server_link = format!(
+    format!(
         "https://gametools.network/servers/{}/{}/{}/{}",
-        games.get(&statics.game[..]).unwrap_or(&&statics.game[..]),
+        server_info::api_game(&statics.game),
         gather_type,
-        status.game_id.clone().unwrap_or_default(),
+        game_id,
         statics.platform
-    );
+    )
+}
+
+/// Post the alert in the alert channel of every guild that has it enabled, in
+/// their language.
+pub async fn send(
+    ctx: Context,
+    state: &state::State,
+    image_url: &str,
+    status: server_info::ServerInfo,
+    alert: Alert,
+    server_info: &str,
+) -> Result<(), serenity::Error> {
+    let statics = state.statics();
+    let (title, description) = alert.text("en");
+    state.publish(events::Event::alert(
+        title,
+        &format!("{}\n{}", description, server_info),
+    ));
+    let paths = CreateAttachment::path(image_url).await?;
+    let server_link = server_link(&statics, &status.game_id.clone().unwrap_or_default());
     let footer = CreateEmbedFooter::new(format!("player threshold set to {} players, checks difference of previous {} minutes and in-between",
     statics.min_player_amount, statics.amount_of_prev_request*2));
-    let embed = CreateEmbed::new()
-        .url(server_link)
-        .title(title)
-        .description(description)
-        .footer(footer);
-    let builder = match status.detailed.server_name.contains("AMG") {
-        true => CreateMessage::new().embed(embed.image(format!("attachment://{}", image_url))),
-        false => CreateMessage::new().embed(embed.thumbnail(format!("attachment://{}", image_url))),
-    };
-    ChannelId::new(statics.message_channel)
-        .send_files(&ctx.http, [paths], builder)
-        .await
+    let forecast = state.forecast().describe();
+    let targets = state.guilds.read().unwrap().targets(&statics, alert);
+    for target in targets {
+        let (title, description) = alert.text(&target.lang);
+        let mut embed = CreateEmbed::new()
+            .url(&server_link)
+            .title(title)
+            .description(format!("{}\n{}", description, server_info))
+            .footer(footer.clone());
+        if let Some(forecast) = &forecast {
+            embed = embed.field("Forecast", forecast, false);
+        }
+        let mut builder = match status.detailed.server_name.contains("AMG") {
+            true => CreateMessage::new().embed(embed.image(format!("attachment://{}", image_url))),
+            false => {
+                CreateMessage::new().embed(embed.thumbnail(format!("attachment://{}", image_url)))
+            }
+        };
+        // 0 means no role is configured for this alert
+        if target.role != 0 {
+            builder = builder
+                .content(format!("<@&{}>", target.role))
+                .allowed_mentions(CreateAllowedMentions::new().roles([RoleId::new(target.role)]));
+        }
+        // one guild removing the bot or the channel shouldn't stop the others
+        if let Err(e) = ChannelId::new(target.channel)
+            .send_files(&ctx.http, [paths.clone()], builder)
+            .await
+        {
+            log::error!(
+                "Failed to send alert to channel {}: {:#?}",
+                target.channel,
+                e
+            );
+        }
+    }
+    Ok(())
 }
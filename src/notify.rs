@@ -0,0 +1,165 @@
+use super::{message, server_info::ServerInfo, state::State, store};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    builder::{CreateAllowedMentions, CreateEmbed, CreateMessage},
+    client::Context,
+    model::id::{ChannelId, RoleId, UserId},
+};
+
+/// A user waiting for a map, stored in the subscriptions file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Subscription {
+    pub user: u64,
+    /// ping this role in the alert channel instead of sending the user a dm
+    pub role: Option<u64>,
+    /// guild the role is pinged in, the `guild` of the bot if not set
+    #[serde(default)]
+    pub guild: Option<u64>,
+    pub map: String,
+    /// any mode if not set
+    pub mode: Option<String>,
+    pub players: i32,
+    /// start of the last round this subscription was notified for
+    #[serde(default)]
+    pub notified_round: i64,
+}
+
+impl Subscription {
+    fn same_target(&self, other: &Subscription) -> bool {
+        self.user == other.user
+            && self.role == other.role
+            && self.map.eq_ignore_ascii_case(&other.map)
+            && self.mode == other.mode
+    }
+
+    fn matches(&self, status: &ServerInfo) -> bool {
+        self.map.eq_ignore_ascii_case(&status.detailed.server_map)
+            && self
+                .mode
+                .as_ref()
+                .map(|mode| mode.eq_ignore_ascii_case(&status.detailed.map_mode))
+                .unwrap_or(true)
+            && status.detailed.current_players >= self.players
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{}{} with {}+ players{}",
+            self.map,
+            match &self.mode {
+                Some(mode) => format!(" - {}", mode),
+                None => "".to_string(),
+            },
+            self.players,
+            match self.role {
+                Some(role) => format!(" (pings <@&{}>)", role),
+                None => "".to_string(),
+            }
+        )
+    }
+}
+
+/// All map subscriptions, saved to disk on every change.
+pub struct Subscriptions {
+    path: String,
+    list: Vec<Subscription>,
+}
+
+impl Subscriptions {
+    pub fn load(path: &str) -> Subscriptions {
+        Subscriptions {
+            path: path.to_string(),
+            list: store::load(path),
+        }
+    }
+
+    /// Add a subscription, replacing the one for the same map and mode.
+    pub fn add(&mut self, subscription: Subscription) {
+        self.list
+            .retain(|existing| !existing.same_target(&subscription));
+        self.list.push(subscription);
+        store::save(&self.path, &self.list);
+    }
+
+    /// Remove the subscriptions of a user for a map, returns how many there
+    /// were.
+    pub fn remove(&mut self, user: u64, map: &str) -> usize {
+        let before = self.list.len();
+        self.list
+            .retain(|existing| existing.user != user || !existing.map.eq_ignore_ascii_case(map));
+        let removed = before - self.list.len();
+        if removed > 0 {
+            store::save(&self.path, &self.list);
+        }
+        removed
+    }
+
+    pub fn of_user(&self, user: u64) -> Vec<&Subscription> {
+        self.list.iter().filter(|sub| sub.user == user).collect()
+    }
+}
+
+async fn notify(ctx: &Context, state: &State, sub: &Subscription, status: &ServerInfo) {
+    let statics = state.statics();
+    let channel = state.guild_config(sub.guild).channel;
+    let embed = CreateEmbed::new()
+        .title(format!("{} is up!", status.detailed.server_map))
+        .url(message::server_link(
+            &statics,
+            &status.game_id.clone().unwrap_or_default(),
+        ))
+        .description(format!(
+            "{} is playing {} - {} with {}/{} players",
+            statics.server_name,
+            status.detailed.server_map,
+            status.detailed.map_mode,
+            status.detailed.current_players,
+            status.detailed.max_players
+        ));
+    let result = match sub.role {
+        // a dm when the guild of the role has no alert channel (anymore)
+        Some(role) if channel != 0 => ChannelId::new(channel)
+            .send_message(
+                &ctx.http,
+                CreateMessage::new()
+                    .content(format!("<@&{}>", role))
+                    .allowed_mentions(CreateAllowedMentions::new().roles([RoleId::new(role)]))
+                    .embed(embed),
+            )
+            .await
+            .map(|_| ()),
+        _ => UserId::new(sub.user)
+            .direct_message(&ctx.http, CreateMessage::new().embed(embed))
+            .await
+            .map(|_| ()),
+    };
+    if let Err(e) = result {
+        log::error!("Failed to send map notification to {}: {:#?}", sub.user, e);
+    }
+}
+
+/// Notify everyone waiting for the current map once per round, as soon as
+/// it has enough players.
+pub async fn check(ctx: &Context, state: &State, status: &ServerInfo) {
+    let round = match state.rotation.read().unwrap().current() {
+        Some(current) => current.timestamp,
+        None => return,
+    };
+    let due: Vec<Subscription> = {
+        let mut subscriptions = state.subscriptions.write().unwrap();
+        let mut due = Vec::new();
+        for sub in subscriptions.list.iter_mut() {
+            if sub.notified_round != round && sub.matches(status) {
+                sub.notified_round = round;
+                due.push(sub.clone());
+            }
+        }
+        if !due.is_empty() {
+            store::save(&subscriptions.path, &subscriptions.list);
+        }
+        due
+    };
+    for sub in &due {
+        notify(ctx, state, sub, status).await;
+    }
+}
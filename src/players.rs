@@ -0,0 +1,217 @@
+use super::{
+    digest::format_duration,
+    message,
+    server_info::{api_game, client, ServerInfo},
+    state::State,
+};
+use anyhow::Result;
+use chrono::Utc;
+use reqwest::Url;
+use serde::Deserialize;
+use serenity::{
+    all::{ButtonStyle, ComponentInteraction},
+    builder::{
+        CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, EditInteractionResponse,
+    },
+    client::Context,
+};
+
+/// A player on the server as listed by the gametools players endpoint.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Player {
+    pub name: String,
+    #[serde(default)]
+    pub rank: Option<i32>,
+    #[serde(default, alias = "squad_id")]
+    pub squad: Option<i64>,
+    /// battlelog gives micro seconds, some games milli seconds
+    #[serde(default)]
+    pub join_time: Option<i64>,
+    #[serde(default)]
+    pub player_id: Option<u64>,
+    #[serde(default)]
+    pub platoon: Option<String>,
+}
+
+impl Player {
+    /// Seconds the player has been on the server.
+    pub fn time_on_server(&self, now: i64) -> Option<i64> {
+        let joined = match self.join_time? {
+            time if time > 100_000_000_000_000 => time / 1_000_000,
+            time if time > 100_000_000_000 => time / 1_000,
+            time => time,
+        };
+        match joined > 0 {
+            true => Some((now - joined).max(0)),
+            false => None,
+        }
+    }
+
+    /// Name with the platoon tag in front, like in game.
+    pub fn display_name(&self) -> String {
+        match self
+            .platoon
+            .as_deref()
+            .filter(|platoon| !platoon.is_empty())
+        {
+            Some(platoon) => format!("[{}]{}", platoon, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Team {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub players: Vec<Player>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlayerList {
+    pub teams: Vec<Team>,
+}
+
+impl PlayerList {
+    /// Players of all teams.
+    pub fn all(&self) -> impl Iterator<Item = &Player> {
+        self.teams.iter().flat_map(|team| team.players.iter())
+    }
+}
+
+/// If gametools has a player list for the game of the server.
+pub fn supported(statics: &message::Static) -> bool {
+    matches!(api_game(&statics.game), "bf1" | "bfv" | "bf4")
+}
+
+pub async fn fetch(statics: &message::Static, status: &ServerInfo) -> Result<PlayerList> {
+    let mut url = Url::parse(
+        &format!(
+            "https://api.gametools.network/{}/players/",
+            api_game(&statics.game)
+        )[..],
+    )
+    .unwrap();
+    url.query_pairs_mut()
+        .append_pair("gameid", &status.game_id.clone().unwrap_or_default())
+        .append_pair("platform", &statics.platform);
+    let response = client()
+        .get(url)
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    if let Some(errors) = response.get("errors") {
+        anyhow::bail!("Failed to get the player list: {}", errors)
+    }
+    Ok(serde_json::from_value::<PlayerList>(response)?)
+}
+
+/// Embed of a single team, with buttons to go to the other teams.
+pub fn page(list: &PlayerList, page: usize, status: &ServerInfo) -> (CreateEmbed, CreateActionRow) {
+    let page = page.min(list.teams.len().saturating_sub(1));
+    let now = Utc::now().timestamp();
+    let mut embed = CreateEmbed::new().title(&status.detailed.server_name);
+    if let Some(team) = list.teams.get(page) {
+        let mut players: Vec<&Player> = team.players.iter().collect();
+        players
+            .sort_by_key(|player| (player.squad.unwrap_or(i64::MAX), player.name.to_lowercase()));
+        let lines = players
+            .iter()
+            .map(|player| {
+                format!(
+                    "`{:>3}` `{:>3}` {} - {}",
+                    player
+                        .squad
+                        .map(|squad| squad.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    player
+                        .rank
+                        .map(|rank| rank.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    player.display_name(),
+                    player
+                        .time_on_server(now)
+                        .map(format_duration)
+                        .unwrap_or_else(|| "-".to_string())
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        embed = embed
+            .description(format!(
+                "**{}** ({} players)\n`squad` `rank` name - time on server\n{}",
+                match team.name.is_empty() {
+                    true => format!("Team {}", page + 1),
+                    false => team.name.clone(),
+                },
+                team.players.len(),
+                lines
+            ))
+            .footer(CreateEmbedFooter::new(format!(
+                "team {} of {}",
+                page + 1,
+                list.teams.len()
+            )));
+    }
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("players:{}", page.saturating_sub(1)))
+            .label("Previous team")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(format!("players:{}", page + 1))
+            .label("Next team")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= list.teams.len()),
+    ]);
+    (embed, buttons)
+}
+
+/// Show another team of the `/players` message, with a fresh player list.
+async fn turn_page(ctx: &Context, state: &State, component: &ComponentInteraction) -> Result<()> {
+    let page = component
+        .data
+        .custom_id
+        .trim_start_matches("players:")
+        .parse::<usize>()
+        .unwrap_or(0);
+    let status = match state.current_status() {
+        Some(status) => status,
+        None => {
+            component
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("The server can't be found right now")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    // the player list can take longer than discord waits for a response
+    component
+        .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+        .await?;
+    let list = fetch(&state.statics(), &status).await?;
+    let (embed, buttons) = self::page(&list, page, &status);
+    component
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new()
+                .embed(embed)
+                .components(vec![buttons]),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn handle(ctx: &Context, state: &State, component: &ComponentInteraction) {
+    if let Err(e) = turn_page(ctx, state, component).await {
+        log::error!("Failed to show player list page: {:#?}", e);
+    }
+}
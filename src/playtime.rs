@@ -0,0 +1,121 @@
+use super::{players::PlayerList, store};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Sessions are kept this long for the weekly and monthly leaderboards, the
+/// all-time totals are kept forever.
+const SESSION_DAYS: i64 = 31;
+/// The file is rewritten at most this often.
+const SAVE_SECS: i64 = 5 * 60;
+
+/// Time a player spent on the server.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Regular {
+    pub name: String,
+    pub total_secs: i64,
+    pub session_count: u32,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    /// start and end of the recent sessions
+    pub sessions: Vec<(i64, i64)>,
+}
+
+impl Regular {
+    /// Seconds played since the given unix timestamp.
+    pub fn played_since(&self, timestamp: i64) -> i64 {
+        self.sessions
+            .iter()
+            .map(|(start, end)| (end - start.max(&timestamp)).max(0))
+            .sum()
+    }
+}
+
+/// Time played per player, saved every few minutes.
+pub struct Playtime {
+    path: String,
+    players: HashMap<String, Regular>,
+    last_save: i64,
+}
+
+impl Playtime {
+    pub fn load(path: &str) -> Playtime {
+        Playtime {
+            path: path.to_string(),
+            players: store::load(path),
+            last_save: 0,
+        }
+    }
+
+    /// Count the time since the last update for everyone still on the server,
+    /// start a new session for who just joined.
+    pub fn update(&mut self, list: &PlayerList) {
+        let now = Utc::now().timestamp();
+        for player in list.all() {
+            let regular = self
+                .players
+                .entry(player.name.to_lowercase())
+                .or_insert_with(|| Regular {
+                    first_seen: now,
+                    ..Regular::default()
+                });
+            regular.name = player.name.clone();
+            match regular.sessions.last_mut() {
+                // the same session if the bot saw them on the last updates
+                Some(session) if now - regular.last_seen <= 5 * 60 => {
+                    regular.total_secs += now - session.1;
+                    session.1 = now;
+                }
+                _ => {
+                    regular.sessions.push((now, now));
+                    regular.session_count += 1;
+                }
+            }
+            regular.last_seen = now;
+        }
+
+        if now - self.last_save >= SAVE_SECS {
+            let oldest = now - SESSION_DAYS * 24 * 60 * 60;
+            for regular in self.players.values_mut() {
+                regular.sessions.retain(|(_, end)| *end >= oldest);
+            }
+            store::save(&self.path, &self.players);
+            self.last_save = now;
+        }
+    }
+
+    /// Most played players since the timestamp, or of all time if none.
+    pub fn top(&self, since: Option<i64>, count: usize) -> Vec<(&Regular, i64)> {
+        let mut top: Vec<(&Regular, i64)> = self
+            .players
+            .values()
+            .map(|regular| match since {
+                Some(since) => (regular, regular.played_since(since)),
+                None => (regular, regular.total_secs),
+            })
+            .filter(|(_, secs)| *secs > 0)
+            .collect();
+        top.sort_by_key(|(_, secs)| std::cmp::Reverse(*secs));
+        top.truncate(count);
+        top
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Regular> {
+        self.players.get(&name.to_lowercase())
+    }
+
+    /// Names of players starting with or containing the text, for
+    /// autocomplete.
+    pub fn search(&self, text: &str) -> Vec<String> {
+        let text = text.to_lowercase();
+        let mut names: Vec<String> = self
+            .players
+            .iter()
+            .filter(|(key, _)| key.contains(&text))
+            .map(|(_, regular)| regular.name.clone())
+            .collect();
+        names.sort_by_key(|name| (!name.to_lowercase().starts_with(&text), name.to_lowercase()));
+        names.truncate(25);
+        names
+    }
+}
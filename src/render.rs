@@ -0,0 +1,225 @@
+use super::{message, server_info};
+use anyhow::{Context, Result};
+use image::ImageReader;
+use std::collections::HashMap;
+
+const USAGE: &str = "usage: discord_bot render [--info server_info.json] [--game tunguska] \
+[--map-image map.jpg] [--small-mode CQ] [--favorites 123] [--name NAME] [--map MAP] \
+[--mode MODE] [--players 58] [--max-players 64] [--queue 3] [--region EU] \
+[--samples 40,52,58] [--animated yes] [--out ./render]";
+
+fn parse_args(args: &[String]) -> Result<HashMap<String, String>> {
+    let mut options = HashMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let key = match arg.strip_prefix("--") {
+            Some(key) => key,
+            None => anyhow::bail!("unexpected argument {:?}\n{}", arg, USAGE),
+        };
+        let value = match args.next() {
+            Some(value) => value,
+            None => anyhow::bail!("--{} needs a value\n{}", key, USAGE),
+        };
+        options.insert(key.to_string(), value.to_string());
+    }
+    Ok(options)
+}
+
+fn parse_number(options: &HashMap<String, String>, key: &str, default: i32) -> Result<i32> {
+    match options.get(key) {
+        Some(value) => value
+            .parse::<i32>()
+            .with_context(|| format!("--{} wasn't given an integer!", key)),
+        None => Ok(default),
+    }
+}
+
+/// Run the gen_img pipeline on local files and write the avatar, embed image
+/// and banner to the output directory, without logging in to discord.
+pub async fn run(args: &[String]) -> Result<()> {
+    let options = parse_args(args)?;
+
+    let mut status = match options.get("info") {
+        Some(path) => serde_json::from_str::<server_info::ServerInfo>(
+            &std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?,
+        )
+        .with_context(|| format!("{} isn't a valid ServerInfo json", path))?,
+        None => server_info::ServerInfo {
+            game_id: None,
+            detailed: server_info::DetailedInfo {
+                current_players: 0,
+                max_players: 64,
+                in_que: None,
+                in_spectator: None,
+                small_mode: "CQ".to_string(),
+                server_name: "Preview server".to_string(),
+                server_map: "Amiens".to_string(),
+                map_url: "".to_string(),
+                map_mode: "Conquest".to_string(),
+                region: "EU".to_string(),
+                favorites: "0".to_string(),
+                fake_players: None,
+            },
+        },
+    };
+    let detailed = &mut status.detailed;
+    if let Some(small_mode) = options.get("small-mode") {
+        detailed.small_mode = small_mode.to_string();
+    }
+    if let Some(favorites) = options.get("favorites") {
+        detailed.favorites = favorites.to_string();
+    }
+    if let Some(name) = options.get("name") {
+        detailed.server_name = name.to_string();
+    }
+    if let Some(map) = options.get("map") {
+        detailed.server_map = map.to_string();
+    }
+    if let Some(mode) = options.get("mode") {
+        detailed.map_mode = mode.to_string();
+    }
+    if let Some(region) = options.get("region") {
+        detailed.region = region.to_string();
+    }
+    detailed.current_players = parse_number(&options, "players", detailed.current_players)?;
+    detailed.max_players = parse_number(&options, "max-players", detailed.max_players)?;
+    if options.contains_key("queue") {
+        detailed.in_que = Some(parse_number(&options, "queue", 0)?);
+    }
+
+    let samples = match options.get("samples") {
+        Some(samples) => samples
+            .split(',')
+            .map(|sample| sample.trim().parse::<i32>())
+            .collect::<Result<Vec<i32>, _>>()
+            .context("--samples wasn't given a list of integers!")?,
+        None => Vec::new(),
+    };
+
+    let statics = message::Static {
+        game: options
+            .get("game")
+            .cloned()
+            .unwrap_or_else(|| "tunguska".to_string()),
+        animated_avatar: options
+            .get("animated")
+            .cloned()
+            .unwrap_or_else(|| "no".to_string()),
+        ..Default::default()
+    };
+
+    let map = match options.get("map-image") {
+        Some(path) => ImageReader::open(path)
+            .with_context(|| format!("Failed to open {}", path))?
+            .with_guessed_format()?
+            .decode()?,
+        None if !status.detailed.map_url.is_empty() => server_info::download_map(&status).await?,
+        None => anyhow::bail!(
+            "--map-image is needed without a map url in --info\n{}",
+            USAGE
+        ),
+    };
+
+    let out_dir = options
+        .get("out")
+        .cloned()
+        .unwrap_or_else(|| "./render".to_string());
+    std::fs::create_dir_all(&out_dir)?;
+
+    let avatar = server_info::render_img(map, &status, &statics, &samples, &out_dir, true)?;
+    log::info!("Avatar written to {}", avatar);
+    log::info!("Embed images and banner written to {}", out_dir);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+    use std::path::Path;
+
+    /// Images rendered from a fixed status, compared against the ones in
+    /// `testdata/golden`. Run with `UPDATE_GOLDEN=1` to rewrite them after an
+    /// intended change to the images.
+    const GOLDEN: [&str; 4] = [
+        "map_mode.jpg",
+        "info_image.jpg",
+        "only_favorites_image.jpg",
+        "banner.png",
+    ];
+
+    fn status() -> server_info::ServerInfo {
+        server_info::ServerInfo {
+            game_id: None,
+            detailed: server_info::DetailedInfo {
+                current_players: 58,
+                max_players: 64,
+                in_que: Some(3),
+                in_spectator: None,
+                small_mode: "CQ".to_string(),
+                server_name: "Golden server".to_string(),
+                server_map: "Amiens".to_string(),
+                map_url: "".to_string(),
+                map_mode: "Conquest".to_string(),
+                region: "EU".to_string(),
+                favorites: "123".to_string(),
+                fake_players: None,
+            },
+        }
+    }
+
+    /// A gradient instead of a map, so the images don't depend on a download.
+    fn map() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(480, 270, |x, y| {
+            Rgb([(x / 2) as u8, (y * 255 / 270) as u8, 120])
+        }))
+    }
+
+    /// Pixels that clearly differ, jpeg encoding can move a pixel a bit.
+    fn difference(a: &DynamicImage, b: &DynamicImage) -> usize {
+        let (a, b) = (a.to_rgba8(), b.to_rgba8());
+        a.pixels()
+            .zip(b.pixels())
+            .filter(|(a, b)| {
+                a.0.iter()
+                    .zip(b.0.iter())
+                    .any(|(a, b)| (*a as i32 - *b as i32).abs() > 24)
+            })
+            .count()
+    }
+
+    #[test]
+    fn images_match_golden() {
+        let out_dir = std::env::temp_dir().join(format!("render-test-{}", std::process::id()));
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let out_dir = out_dir.to_str().unwrap().to_string();
+        let statics = message::Static {
+            game: "tunguska".to_string(),
+            ..Default::default()
+        };
+        server_info::render_img(map(), &status(), &statics, &[40, 52, 58], &out_dir, true).unwrap();
+
+        let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/golden");
+        let update = std::env::var("UPDATE_GOLDEN").is_ok();
+        for name in GOLDEN.iter() {
+            let rendered = image::open(format!("{}/{}", out_dir, name)).unwrap();
+            // stored as png so the golden images don't lose detail
+            let golden_path = golden_dir.join(name).with_extension("png");
+            if update {
+                std::fs::create_dir_all(&golden_dir).unwrap();
+                rendered.save(&golden_path).unwrap();
+                continue;
+            }
+            let golden = image::open(&golden_path).unwrap();
+            assert_eq!(rendered.dimensions(), golden.dimensions(), "{}", name);
+            let difference = difference(&rendered, &golden);
+            assert!(
+                difference <= 10,
+                "{} differs from the golden image in {} pixels",
+                name,
+                difference
+            );
+        }
+        let _ = std::fs::remove_dir_all(&out_dir);
+    }
+}
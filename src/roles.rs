@@ -0,0 +1,107 @@
+use super::{guilds::GuildConfig, state::State};
+use serenity::{
+    all::{ButtonStyle, ComponentInteraction},
+    builder::{
+        CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage,
+    },
+    client::Context,
+    model::id::RoleId,
+};
+
+/// The alert roles people can give themselves in a guild, as the button id,
+/// label, configured role and what it pings for.
+fn alert_roles(config: &GuildConfig) -> Vec<(&'static str, &'static str, u64, &'static str)> {
+    vec![
+        // pinged with the seeding calls and the running and pre-round alerts
+        (
+            "role:seeding",
+            "Notify me when it starts up",
+            config.seeding_role,
+            "the server needs seeders or a round gets going",
+        ),
+        (
+            "role:queue",
+            "Notify me when queue clears",
+            config.queue_role,
+            "you can join without a queue",
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, _, role, _)| *role != 0)
+    .collect()
+}
+
+/// Buttons for every configured alert role, none if there aren't any.
+pub fn buttons(config: &GuildConfig) -> Option<CreateActionRow> {
+    let buttons: Vec<CreateButton> = alert_roles(config)
+        .into_iter()
+        .map(|(id, label, _, _)| {
+            CreateButton::new(id)
+                .label(label)
+                .style(ButtonStyle::Secondary)
+        })
+        .collect();
+    match buttons.is_empty() {
+        true => None,
+        false => Some(CreateActionRow::Buttons(buttons)),
+    }
+}
+
+/// Give or take the role of the clicked button, returns the answer for the
+/// member.
+async fn toggle(ctx: &Context, state: &State, component: &ComponentInteraction) -> String {
+    let config = state.guild_config(component.guild_id.map(|guild| guild.get()));
+    let (role, reason) = match alert_roles(&config)
+        .into_iter()
+        .find(|(id, _, _, _)| *id == component.data.custom_id)
+    {
+        Some((_, _, role, reason)) => (RoleId::new(role), reason),
+        None => return "This role isn't set for this server anymore".to_string(),
+    };
+    let member = match &component.member {
+        Some(member) => member,
+        None => return "Roles can only be given in a server".to_string(),
+    };
+    let result = match member.roles.contains(&role) {
+        true => member
+            .remove_role(&ctx.http, role)
+            .await
+            .map(|_| format!("You won't be pinged anymore when {}", reason)),
+        false => member
+            .add_role(&ctx.http, role)
+            .await
+            .map(|_| format!("You'll be pinged when {}", reason)),
+    };
+    match result {
+        Ok(content) => content,
+        Err(e) => {
+            log::error!("Failed to toggle role {}: {:#?}", role, e);
+            format!(
+                "I couldn't change your roles. The bot needs the manage roles permission and \
+its role has to be above <@&{}>, ask an admin to check it",
+                role
+            )
+        }
+    }
+}
+
+pub async fn handle(ctx: &Context, state: &State, component: &ComponentInteraction) {
+    let content = toggle(ctx, state, component).await;
+    if let Err(e) = component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await
+    {
+        log::error!(
+            "Failed to answer role button {}: {:#?}",
+            component.data.custom_id,
+            e
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use super::history::Sample;
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+/// Rounds longer than this mean the bot missed the map change.
+const MAX_ROUND: i64 = 3 * 60 * 60;
+
+/// The moment the server switched to a new map, stored one json object per
+/// line.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MapChange {
+    /// unix timestamp in seconds
+    pub timestamp: i64,
+    pub map: String,
+    pub mode: String,
+    /// false when the bot wasn't watching the minute before, so the round
+    /// could have started earlier
+    pub observed: bool,
+}
+
+impl MapChange {
+    fn same_map(&self, other: &MapChange) -> bool {
+        self.map == other.map && self.mode == other.mode
+    }
+
+    pub fn name(&self) -> String {
+        format!("{} - {}", self.map, self.mode)
+    }
+}
+
+/// What the rotation is expected to do next.
+pub struct Prediction {
+    pub next: String,
+    /// unix timestamp the current round is expected to end, none if this map
+    /// was never seen ending
+    pub eta: Option<i64>,
+}
+
+/// Map changes of the last `history_days`, kept in memory and appended to the
+/// rotation file.
+pub struct Rotation {
+    path: String,
+    changes: Vec<MapChange>,
+}
+
+impl Rotation {
+    pub fn load(path: &str, retention_days: i64) -> Rotation {
+        let oldest = Utc::now().timestamp() - retention_days * 24 * 60 * 60;
+        let changes = match fs::read_to_string(path) {
+            Ok(content) => content
+                .lines()
+                .filter_map(|line| serde_json::from_str::<MapChange>(line).ok())
+                .filter(|change| change.timestamp >= oldest)
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        let rotation = Rotation {
+            path: path.to_string(),
+            changes,
+        };
+        if let Err(e) = rotation.save() {
+            log::error!("Failed to rewrite rotation file: {:#?}", e);
+        }
+        rotation
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut content = String::new();
+        for change in &self.changes {
+            content.push_str(&serde_json::to_string(change)?);
+            content.push('\n');
+        }
+        fs::write(&self.path, content)?;
+        Ok(())
+    }
+
+    /// Record the map of a new sample if it changed, `previous` is the sample
+    /// before it. Returns the change if there was one.
+    pub fn update(&mut self, sample: &Sample, previous: Option<&Sample>) -> Option<MapChange> {
+        if sample.map.is_empty() {
+            return None;
+        }
+        let change = MapChange {
+            timestamp: sample.timestamp,
+            map: sample.map.clone(),
+            mode: sample.mode.clone(),
+            observed: previous
+                .map(|previous| {
+                    !previous.map.is_empty() && sample.timestamp - previous.timestamp <= 5 * 60
+                })
+                .unwrap_or(false),
+        };
+        if self
+            .changes
+            .last()
+            .map(|last| last.same_map(&change))
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| {
+                writeln!(
+                    file,
+                    "{}",
+                    serde_json::to_string(&change).unwrap_or_default()
+                )
+            });
+        if let Err(e) = result {
+            log::error!("Failed to save map change: {:#?}", e);
+        }
+        self.changes.push(change.clone());
+        Some(change)
+    }
+
+    pub fn current(&self) -> Option<&MapChange> {
+        self.changes.last()
+    }
+
+    /// The last map changes, newest first.
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &MapChange> {
+        self.changes.iter().rev().take(count)
+    }
+
+    /// Rounds that were watched from start to end, as the map and the seconds
+    /// it lasted.
+    pub fn rounds(&self) -> impl Iterator<Item = (&MapChange, i64)> {
+        self.changes.windows(2).filter_map(|pair| {
+            let length = pair[1].timestamp - pair[0].timestamp;
+            match pair[0].observed && pair[1].observed && length <= MAX_ROUND {
+                true => Some((&pair[0], length)),
+                false => None,
+            }
+        })
+    }
+
+    /// Average round length in seconds per map.
+    pub fn round_lengths(&self) -> HashMap<String, i64> {
+        let mut totals: HashMap<String, (i64, i64)> = HashMap::new();
+        for (change, length) in self.rounds() {
+            let total = totals.entry(change.name()).or_default();
+            total.0 += length;
+            total.1 += 1;
+        }
+        totals
+            .into_iter()
+            .map(|(name, (length, count))| (name, length / count))
+            .collect()
+    }
+
+    /// The map that most often followed the current one, with the time the
+    /// current round should end.
+    pub fn predict(&self) -> Option<Prediction> {
+        let current = self.current()?;
+        let mut followers: HashMap<String, (usize, i64)> = HashMap::new();
+        for pair in self.changes.windows(2) {
+            if pair[0].same_map(current) && !pair[1].same_map(current) {
+                let follower = followers.entry(pair[1].name()).or_default();
+                follower.0 += 1;
+                // prefer the most recent on a tie
+                follower.1 = follower.1.max(pair[1].timestamp);
+            }
+        }
+        let next = followers
+            .into_iter()
+            .max_by_key(|(_, (count, last))| (*count, *last))?
+            .0;
+        let eta = self
+            .round_lengths()
+            .get(&current.name())
+            .map(|length| current.timestamp + length);
+        Some(Prediction { next, eta })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{history, server_info::ServerState};
+
+    fn change(minute: i64, map: &str, observed: bool) -> MapChange {
+        MapChange {
+            timestamp: 1_700_000_000 + minute * 60,
+            map: map.to_string(),
+            mode: "Conquest".to_string(),
+            observed,
+        }
+    }
+
+    fn rotation(changes: Vec<MapChange>) -> Rotation {
+        Rotation {
+            path: String::new(),
+            changes,
+        }
+    }
+
+    fn live(minute: i64, map: &str) -> Sample {
+        history::sample(minute, 64, 0, map, ServerState::Live)
+    }
+
+    #[test]
+    fn round_lengths_skip_unobserved_and_long_rounds() {
+        let rounds = rotation(vec![
+            change(0, "Amiens", false),
+            change(30, "Verdun", true),
+            change(70, "Amiens", true),
+            change(110, "Verdun", true),
+            // missed the change, this round started somewhere before
+            change(150, "Amiens", false),
+            change(180, "Verdun", true),
+            // longer than a round can be
+            change(400, "Amiens", true),
+        ]);
+        let lengths = rounds.round_lengths();
+        assert_eq!(lengths.get("Verdun - Conquest"), Some(&(40 * 60)));
+        assert_eq!(lengths.get("Amiens - Conquest"), Some(&(40 * 60)));
+        assert_eq!(lengths.len(), 2);
+    }
+
+    #[test]
+    fn predict_most_common_follower() {
+        let rounds = rotation(vec![
+            change(0, "Amiens", true),
+            change(30, "Verdun", true),
+            change(60, "Amiens", true),
+            change(90, "Verdun", true),
+            change(120, "Amiens", true),
+            change(150, "Fao", true),
+            change(180, "Amiens", true),
+        ]);
+        let prediction = rounds.predict().unwrap();
+        assert_eq!(prediction.next, "Verdun - Conquest");
+        assert_eq!(prediction.eta, Some(1_700_000_000 + 210 * 60));
+    }
+
+    #[test]
+    fn predict_prefers_the_most_recent_on_a_tie() {
+        let fao_last = rotation(vec![
+            change(0, "Amiens", true),
+            change(30, "Verdun", true),
+            change(60, "Amiens", true),
+            change(90, "Fao", true),
+            change(120, "Amiens", true),
+        ]);
+        assert_eq!(fao_last.predict().unwrap().next, "Fao - Conquest");
+
+        let verdun_last = rotation(vec![
+            change(0, "Amiens", true),
+            change(30, "Fao", true),
+            change(60, "Amiens", true),
+            change(90, "Verdun", true),
+            change(120, "Amiens", true),
+        ]);
+        assert_eq!(verdun_last.predict().unwrap().next, "Verdun - Conquest");
+    }
+
+    #[test]
+    fn predict_needs_a_follower() {
+        assert!(rotation(Vec::new()).predict().is_none());
+        assert!(rotation(vec![change(0, "Amiens", true)])
+            .predict()
+            .is_none());
+    }
+
+    #[test]
+    fn update_records_only_map_changes() {
+        let path = std::env::temp_dir().join(format!("rotation-test-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = fs::remove_file(&path);
+        let mut rotation = Rotation::load(&path, 7);
+
+        let first = live(0, "Amiens");
+        let change = rotation.update(&first, None).unwrap();
+        // nothing was watched before the first sample
+        assert!(!change.observed);
+
+        let same = live(1, "Amiens");
+        assert!(rotation.update(&same, Some(&first)).is_none());
+        assert!(rotation.update(&live(2, ""), Some(&same)).is_none());
+
+        let next = live(2, "Verdun");
+        assert!(rotation.update(&next, Some(&same)).unwrap().observed);
+        // too long since the previous sample to know when it changed
+        assert!(
+            !rotation
+                .update(&live(20, "Fao"), Some(&next))
+                .unwrap()
+                .observed
+        );
+
+        let reloaded = Rotation::load(&path, 100_000);
+        let _ = fs::remove_file(&path);
+        let maps: Vec<&str> = reloaded
+            .recent(5)
+            .map(|change| change.map.as_str())
+            .collect();
+        assert_eq!(maps, vec!["Fao", "Verdun", "Amiens"]);
+    }
+}
@@ -0,0 +1,255 @@
+use super::{
+    message,
+    server_info::{ServerInfo, ServerState},
+    state::State,
+};
+use anyhow::Result;
+use chrono::{Timelike, Utc};
+use serenity::{
+    all::{ButtonStyle, ComponentInteraction},
+    builder::{
+        CreateActionRow, CreateAllowedMentions, CreateButton, CreateEmbed,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, EditMessage,
+    },
+    client::Context,
+    model::id::{ChannelId, MessageId, RoleId},
+};
+
+/// A running "seed needed" call.
+#[derive(Clone, Debug)]
+pub struct Campaign {
+    /// the alert channel of the `guild` of the bot when it was posted
+    channel: ChannelId,
+    message: MessageId,
+    /// discord users that clicked "I'm seeding"
+    seeders: Vec<u64>,
+    players: i32,
+}
+
+/// The current call and when the last one was posted.
+#[derive(Default)]
+pub struct Seeding {
+    campaign: Option<Campaign>,
+    last_call: i64,
+}
+
+/// If the current local hour is in the configured seeding hours, the end can
+/// be past midnight like 22-2.
+fn in_hours(statics: &message::Static) -> bool {
+    let (start, end) = match statics.seeding_hours {
+        Some(hours) => hours,
+        None => return false,
+    };
+    let hour = Utc::now().with_timezone(&statics.timezone).hour();
+    match start <= end {
+        true => hour >= start && hour < end,
+        false => hour >= start || hour < end,
+    }
+}
+
+fn seeders_text(seeders: &[u64]) -> String {
+    match seeders.is_empty() {
+        true => "nobody yet, be the first!".to_string(),
+        false => seeders
+            .iter()
+            .map(|seeder| format!("<@{}>", seeder))
+            .collect::<Vec<String>>()
+            .join(" "),
+    }
+}
+
+fn call_embed(statics: &message::Static, status: &ServerInfo, seeders: &[u64]) -> CreateEmbed {
+    CreateEmbed::new()
+        .title("Seed needed!")
+        .url(message::server_link(
+            statics,
+            &status.game_id.clone().unwrap_or_default(),
+        ))
+        .description(format!(
+            "{} needs players to get going, it goes live at {} players.",
+            statics.server_name, statics.started_amount
+        ))
+        .field(
+            "Players",
+            format!(
+                "{}/{}",
+                status.detailed.current_players, status.detailed.max_players
+            ),
+            true,
+        )
+        .field("Map", &status.detailed.server_map, true)
+        .field("Seeders", seeders_text(seeders), false)
+}
+
+fn seed_button() -> CreateActionRow {
+    CreateActionRow::Buttons(vec![CreateButton::new("seed:join")
+        .label("I'm seeding")
+        .style(ButtonStyle::Success)])
+}
+
+async fn start(ctx: &Context, state: &State, status: &ServerInfo) -> Result<Campaign> {
+    let statics = state.statics();
+    // the role people sign up for with /rolebuttons and /config role
+    let config = state.guild_config(Some(statics.guild));
+    if config.channel == 0 {
+        anyhow::bail!("no alert channel is set");
+    }
+    let mut builder = CreateMessage::new()
+        .embed(call_embed(&statics, status, &[]))
+        .components(vec![seed_button()]);
+    if config.seeding_role != 0 {
+        builder = builder
+            .content(format!("<@&{}>", config.seeding_role))
+            .allowed_mentions(
+                CreateAllowedMentions::new().roles([RoleId::new(config.seeding_role)]),
+            );
+    }
+    let channel = ChannelId::new(config.channel);
+    let message = channel.send_message(&ctx.http, builder).await?;
+    Ok(Campaign {
+        channel,
+        message: message.id,
+        seeders: Vec::new(),
+        players: status.detailed.current_players,
+    })
+}
+
+/// Close the call, thanking the seeders if the server made it.
+async fn finish(ctx: &Context, state: &State, campaign: &Campaign, live: bool) -> Result<()> {
+    let statics = state.statics();
+    let channel = campaign.channel;
+    let embed = CreateEmbed::new()
+        .title(match live {
+            true => "Seeding done, the server is live!",
+            false => "Seeding ended",
+        })
+        .description(&statics.server_name)
+        .field("Seeders", seeders_text(&campaign.seeders), false);
+    channel
+        .edit_message(
+            &ctx.http,
+            campaign.message,
+            EditMessage::new().embed(embed).components(vec![]),
+        )
+        .await?;
+    if live && !campaign.seeders.is_empty() {
+        channel
+            .send_message(
+                &ctx.http,
+                CreateMessage::new()
+                    .content(format!(
+                        "Thanks for seeding {}! {} is live now.",
+                        seeders_text(&campaign.seeders),
+                        statics.server_name
+                    ))
+                    // thank them without pinging everyone again
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Start, update or close the seeding call for the latest update.
+pub async fn update(ctx: &Context, state: &State, status: &ServerInfo) {
+    let statics = state.statics();
+    let now = Utc::now().timestamp();
+    let server_state = ServerState::from_status(status, &statics);
+    let (campaign, last_call) = {
+        let seeding = state.seeding.lock().unwrap();
+        (seeding.campaign.clone(), seeding.last_call)
+    };
+
+    let result = match campaign {
+        None => {
+            let needed = matches!(server_state, ServerState::Empty | ServerState::Seeding);
+            if needed && in_hours(&statics) && now - last_call >= statics.seeding_cooldown * 60 {
+                match start(ctx, state, status).await {
+                    Ok(campaign) => {
+                        let mut seeding = state.seeding.lock().unwrap();
+                        seeding.campaign = Some(campaign);
+                        seeding.last_call = now;
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                }
+            } else {
+                Ok(())
+            }
+        }
+        Some(mut campaign) => {
+            let live = status.detailed.current_players >= statics.started_amount;
+            if live || !in_hours(&statics) {
+                // take it out again, someone could have signed up meanwhile
+                let campaign = state
+                    .seeding
+                    .lock()
+                    .unwrap()
+                    .campaign
+                    .take()
+                    .unwrap_or(campaign);
+                finish(ctx, state, &campaign, live).await
+            } else if campaign.players != status.detailed.current_players {
+                if let Some(current) = state.seeding.lock().unwrap().campaign.as_mut() {
+                    current.players = status.detailed.current_players;
+                    campaign = current.clone();
+                }
+                campaign
+                    .channel
+                    .edit_message(
+                        &ctx.http,
+                        campaign.message,
+                        EditMessage::new().embed(call_embed(&statics, status, &campaign.seeders)),
+                    )
+                    .await
+                    .map(|_| ())
+                    .map_err(anyhow::Error::from)
+            } else {
+                Ok(())
+            }
+        }
+    };
+    if let Err(e) = result {
+        log::error!("Failed to update seeding call: {:#?}", e);
+    }
+}
+
+/// Sign the user up for the running call, or off if they already are.
+async fn join(ctx: &Context, state: &State, component: &ComponentInteraction) -> Result<()> {
+    let user = component.user.id.get();
+    let campaign = {
+        let mut seeding = state.seeding.lock().unwrap();
+        match seeding.campaign.as_mut() {
+            Some(campaign) if campaign.message == component.message.id => {
+                match campaign.seeders.contains(&user) {
+                    true => campaign.seeders.retain(|seeder| *seeder != user),
+                    false => campaign.seeders.push(user),
+                }
+                Some(campaign.clone())
+            }
+            _ => None,
+        }
+    };
+    let response = match (campaign, state.current_status()) {
+        (Some(campaign), Some(status)) => CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new().embed(call_embed(
+                &state.statics(),
+                &status,
+                &campaign.seeders,
+            )),
+        ),
+        _ => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content("This seeding call is over")
+                .ephemeral(true),
+        ),
+    };
+    component.create_response(&ctx.http, response).await?;
+    Ok(())
+}
+
+pub async fn handle(ctx: &Context, state: &State, component: &ComponentInteraction) {
+    if let Err(e) = join(ctx, state, component).await {
+        log::error!("Failed to sign up seeder: {:#?}", e);
+    }
+}
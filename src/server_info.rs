@@ -1,13 +1,25 @@
 use super::message;
-use ab_glyph::{FontRef, PxScale};
+use super::text::{FontChain, TextBox};
+use ab_glyph::{FontArc, PxScale};
 use anyhow::Result;
-use image::{ImageReader, Rgba};
-use imageproc::drawing::draw_text_mut;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::FilterType,
+    Delay, DynamicImage, Frame, ImageReader, Rgba, RgbaImage,
+};
+use imageproc::{
+    drawing::{draw_filled_circle_mut, draw_filled_rect_mut, draw_line_segment_mut, Blend},
+    rect::Rect,
+};
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serenity::{client::Context, gateway::ActivityData};
-use std::io::Cursor;
+use std::{io::Cursor, sync::OnceLock, time::Duration};
+
+/// Longest a gametools request can take, so a hung one can't hold up the
+/// updates.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MainInfo {
@@ -71,12 +83,69 @@ pub struct DetailedInfo {
     pub fake_players: Option<i32>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ServerInfo {
     pub game_id: Option<String>,
     pub detailed: DetailedInfo,
 }
 
+/// How busy the server is, used for the colors of the badge and status page.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerState {
+    Offline,
+    Empty,
+    Seeding,
+    Live,
+    Full,
+}
+
+impl ServerState {
+    pub fn from_status(status: &ServerInfo, statics: &message::Static) -> ServerState {
+        let detailed = &status.detailed;
+        if detailed.current_players >= detailed.max_players || detailed.in_que.unwrap_or(0) > 0 {
+            ServerState::Full
+        } else if detailed.current_players >= statics.started_amount {
+            ServerState::Live
+        } else if detailed.current_players > 5 {
+            ServerState::Seeding
+        } else {
+            ServerState::Empty
+        }
+    }
+
+    pub fn color(&self) -> &'static str {
+        match self {
+            ServerState::Offline => "#9f9f9f",
+            ServerState::Empty => "#e05d44",
+            ServerState::Seeding => "#dfb317",
+            ServerState::Live => "#4c1",
+            ServerState::Full => "#007ec6",
+        }
+    }
+}
+
+/// Client shared by the api requests, with a timeout.
+pub fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to create the http client")
+    })
+}
+
+/// Name of the game in the gametools api, the env variable still accepts the
+/// old codenames.
+pub fn api_game(game: &str) -> &str {
+    match game {
+        "tunguska" => "bf1",
+        "casablanca" => "bfv",
+        "kingston" => "bf2042",
+        _ => game,
+    }
+}
+
 async fn request_list(
     statics: &message::Static,
     game: &str,
@@ -118,22 +187,13 @@ async fn request_detailed(
 }
 
 async fn get(statics: message::Static, game_id: &String) -> Result<ServerInfo> {
-    let game;
-    if &statics.game[..] == "tunguska" {
-        game = "bf1"
-    } else if &statics.game[..] == "casablanca" {
-        game = "bfv"
-    } else if &statics.game[..] == "kingston" {
-        game = "bf2042"
-    } else {
-        game = &statics.game[..]
-    }
+    let game = api_game(&statics.game);
 
-    let client = reqwest::Client::new();
+    let client = client();
     // try twice first
-    let mut response = request_list(&statics, game, &client).await?;
+    let mut response = request_list(&statics, game, client).await?;
     if response.get("errors").is_some() {
-        response = request_list(&statics, game, &client).await?;
+        response = request_list(&statics, game, client).await?;
     }
 
     let mut info = json!(null);
@@ -206,9 +266,9 @@ async fn get(statics: message::Static, game_id: &String) -> Result<ServerInfo> {
     // get detailed via old or new game_id
     let detailed = match &statics.game[..] {
         "tunguska" | "bf4" => {
-            let mut detailed_response = request_detailed(&statics, &game_id, game, &client).await?;
+            let mut detailed_response = request_detailed(&statics, &game_id, game, client).await?;
             if detailed_response.get("errors").is_some() {
-                detailed_response = request_detailed(&statics, &game_id, game, &client).await?;
+                detailed_response = request_detailed(&statics, &game_id, game, client).await?;
             }
 
             let mut detailed = serde_json::from_value::<DetailedInfo>(detailed_response)?;
@@ -255,6 +315,24 @@ async fn get(statics: message::Static, game_id: &String) -> Result<ServerInfo> {
     })
 }
 
+/// Players, queue and map as shown in the presence of the bot.
+pub fn activity_text(status: &ServerInfo, statics: &message::Static) -> String {
+    format!(
+        "{}/{}{}{} - {}",
+        status.detailed.current_players,
+        status.detailed.max_players,
+        match status.detailed.in_que.unwrap_or(0) > 0 {
+            true => format!(" [{}]", status.detailed.in_que.unwrap_or(0)),
+            false => "".to_string(),
+        },
+        match &statics.include_spectators[..] == "yes" {
+            true => format!(" ({})", status.detailed.in_spectator.unwrap_or(0)),
+            false => "".to_string(),
+        },
+        status.detailed.server_map
+    )
+}
+
 pub async fn change_name(
     ctx: Context,
     statics: message::Static,
@@ -262,23 +340,10 @@ pub async fn change_name(
 ) -> Result<ServerInfo> {
     let status = match get(statics.clone(), game_id).await {
         Ok(status) => {
-            let server_info = format!(
-                "{}/{}{}{} - {}",
-                status.detailed.current_players,
-                status.detailed.max_players,
-                match status.detailed.in_que.unwrap_or(0) > 0 {
-                    true => format!(" [{}]", status.detailed.in_que.unwrap_or(0)),
-                    false => "".to_string(),
-                },
-                match &statics.include_spectators[..] == "yes" {
-                    true => format!(" ({})", status.detailed.in_spectator.unwrap_or(0)),
-                    false => "".to_string(),
-                },
-                status.detailed.server_map
-            );
-
             // change game activity
-            ctx.set_activity(Some(ActivityData::playing(server_info)));
+            ctx.set_activity(Some(ActivityData::playing(activity_text(
+                &status, &statics,
+            ))));
 
             status
         }
@@ -293,9 +358,8 @@ pub async fn change_name(
     Ok(status)
 }
 
-pub async fn gen_img(status: ServerInfo, statics: message::Static) -> Result<String> {
-    let client = reqwest::Client::new();
-    let img = client
+pub async fn download_map(status: &ServerInfo) -> Result<DynamicImage> {
+    let img = client()
         .get(status.detailed.map_url.replace(
             "[BB_PREFIX]",
             "https://eaassets-a.akamaihd.net/battlelog/battlebinary",
@@ -304,26 +368,50 @@ pub async fn gen_img(status: ServerInfo, statics: message::Static) -> Result<Str
         .await?
         .bytes()
         .await?;
-    let mut img2 = ImageReader::new(Cursor::new(img))
+    Ok(ImageReader::new(Cursor::new(img))
         .with_guessed_format()?
-        .decode()?;
+        .decode()?)
+}
 
-    img2.save("./map.jpg")?;
-    img2 = img2.brighten(-25);
+/// Download the map image and render all images for it, returns the avatar
+/// path and the map image.
+pub async fn gen_img(
+    status: ServerInfo,
+    statics: message::Static,
+    samples: &[i32],
+    avatar_due: bool,
+) -> Result<(String, DynamicImage)> {
+    let map = download_map(&status).await?;
+    let avatar = render_img(map.clone(), &status, &statics, samples, ".", avatar_due)?;
+    Ok((avatar, map))
+}
 
-    let font: FontRef = if &statics.game[..] == "kingston" || &statics.game[..] == "bf2042" {
-        FontRef::try_from_slice(include_bytes!("BF_Modernista-Regular.ttf") as &[u8]).unwrap()
-    } else {
-        FontRef::try_from_slice(include_bytes!("Futura.ttf") as &[u8]).unwrap()
-    };
+/// Draw the avatar and embed images on the map image and save them in
+/// `out_dir`, returns the path of the image used as avatar. The animated
+/// avatar is only made when `avatar_due`, it's the slowest to render.
+pub fn render_img(
+    mut img2: DynamicImage,
+    status: &ServerInfo,
+    statics: &message::Static,
+    samples: &[i32],
+    out_dir: &str,
+    avatar_due: bool,
+) -> Result<String> {
+    img2.save(format!("{}/map.jpg", out_dir))?;
+    if &statics.set_banner_image[..] == "yes" {
+        gen_banner(&img2, status, statics, samples, out_dir)?;
+    }
+    img2 = img2.brighten(-25);
 
-    let small_font = FontRef::try_from_slice(include_bytes!("DejaVuSans.ttf") as &[u8]).unwrap();
+    let font = FontChain::new(title_font(statics));
+    let small_font = FontChain::new(small_font());
 
     let img_size = PxScale {
         x: img2.width() as f32,
         y: img2.height() as f32,
     };
     let mut orig_img2 = img2.clone();
+    let base = img2.clone();
 
     // only smallmode
     let scale = PxScale {
@@ -337,16 +425,16 @@ pub async fn gen_img(status: ServerInfo, statics: message::Static) -> Result<Str
         middle = 3.15;
     }
 
-    draw_text_mut(
+    font.draw_fitted(
         &mut img2,
         Rgba([255u8, 255u8, 255u8, 255u8]),
-        (img_size.x / middle) as i32,
-        (img_size.y / 4.8) as i32,
         scale,
-        &font,
         &status.detailed.small_mode[..],
+        text_area(&img_size, middle, 4.8),
+        false,
     );
-    img2.save("./map_mode.jpg")?;
+    img2.save(format!("{}/map_mode.jpg", out_dir))?;
+    let mode_frame = img2.clone();
 
     // with favorites except bf5
     let small_scale = PxScale {
@@ -354,17 +442,16 @@ pub async fn gen_img(status: ServerInfo, statics: message::Static) -> Result<Str
         y: (img2.height() / 6) as f32,
     };
     if &statics.game[..] == "tunguska" || &statics.game[..] == "bf4" {
-        draw_text_mut(
+        small_font.draw_fitted(
             &mut img2,
             Rgba([255u8, 255u8, 255u8, 255u8]),
-            (img_size.x / 3.5) as i32,
-            (img_size.y / 1.5) as i32,
             small_scale,
-            &small_font,
             &format!("{}{}", "\u{2605}", status.detailed.favorites)[..],
+            text_area(&img_size, 3.5, 1.5),
+            false,
         );
     }
-    img2.save("./info_image.jpg")?;
+    img2.save(format!("{}/info_image.jpg", out_dir))?;
 
     // only favorites except bf5
     let fav_scale = PxScale {
@@ -372,21 +459,288 @@ pub async fn gen_img(status: ServerInfo, statics: message::Static) -> Result<Str
         y: (img2.height() as f32) / 4.5,
     };
     if &statics.game[..] == "tunguska" || &statics.game[..] == "bf4" {
-        draw_text_mut(
+        small_font.draw_fitted(
             &mut orig_img2,
             Rgba([255u8, 255u8, 255u8, 255u8]),
-            (img_size.x / 4.0) as i32,
-            (img_size.y / 2.5) as i32,
             fav_scale,
-            &small_font,
             &format!("{}{}", "\u{2605}", status.detailed.favorites)[..],
+            text_area(&img_size, 4.0, 2.5),
+            false,
         );
     }
-    orig_img2.save("./only_favorites_image.jpg")?;
+    orig_img2.save(format!("{}/only_favorites_image.jpg", out_dir))?;
+
+    if &statics.animated_avatar[..] == "yes" && avatar_due {
+        return gen_animated_avatar(&base, &mode_frame, status, statics, out_dir);
+    }
 
     // get image based on name
     if status.detailed.server_name.contains("AMG") {
-        return Ok(String::from("./only_favorites_image.jpg"));
+        return Ok(format!("{}/only_favorites_image.jpg", out_dir));
+    }
+    Ok(format!("{}/info_image.jpg", out_dir))
+}
+
+/// Render an animated avatar cycling between the map mode, the player count
+/// and (if there is one) the queue, with a pulsing "LIVE" dot in the corner.
+/// Saved as `avatar.gif` in `out_dir`.
+pub fn gen_animated_avatar(
+    base: &DynamicImage,
+    mode_frame: &DynamicImage,
+    status: &ServerInfo,
+    statics: &message::Static,
+    out_dir: &str,
+) -> Result<String> {
+    // discord shows avatars small, quantizing the full map for every frame
+    // is slow for nothing
+    let base = &base.resize(AVATAR_SIZE, AVATAR_SIZE, FilterType::Triangle);
+    let mode_frame = &mode_frame.resize(AVATAR_SIZE, AVATAR_SIZE, FilterType::Triangle);
+    let white = Rgba([255u8, 255u8, 255u8, 255u8]);
+    let font = FontChain::new(title_font(statics));
+    let img_size = PxScale {
+        x: base.width() as f32,
+        y: base.height() as f32,
+    };
+    let big_scale = PxScale {
+        x: img_size.x / 4.0,
+        y: img_size.y / 2.6,
+    };
+
+    let mut players_frame = base.clone();
+    font.draw_fitted(
+        &mut players_frame,
+        white,
+        big_scale,
+        &format!(
+            "{}/{}",
+            status.detailed.current_players, status.detailed.max_players
+        ),
+        text_area(&img_size, 6.0, 3.6),
+        false,
+    );
+    let mut screens = vec![mode_frame.to_rgba8(), players_frame.to_rgba8()];
+
+    let in_que = status.detailed.in_que.unwrap_or(0);
+    if in_que > 0 {
+        let mut queue_frame = base.clone();
+        font.draw_fitted(
+            &mut queue_frame,
+            white,
+            big_scale,
+            &format!("[{}]", in_que),
+            text_area(&img_size, 3.5, 3.6),
+            false,
+        );
+        screens.push(queue_frame.to_rgba8());
+    }
+
+    // every screen is shown twice, once with the live dot lit and once dimmed
+    let radius = (img_size.y / 14.0) as i32;
+    let center = (img_size.x as i32 - radius * 2, radius * 2);
+    let mut frames = Vec::new();
+    for screen in screens {
+        for color in [
+            Rgba([230u8, 30u8, 30u8, 255u8]),
+            Rgba([110u8, 20u8, 20u8, 255u8]),
+        ] {
+            let mut frame = screen.clone();
+            draw_filled_circle_mut(&mut frame, center, radius, color);
+            frames.push(Frame::from_parts(
+                frame,
+                0,
+                0,
+                Delay::from_numer_denom_ms(1000, 1),
+            ));
+        }
+    }
+
+    let path = format!("{}/avatar.gif", out_dir);
+    let mut encoder = GifEncoder::new_with_speed(std::fs::File::create(&path)?, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(frames)?;
+    Ok(path)
+}
+
+/// Largest side of the animated avatar.
+const AVATAR_SIZE: u32 = 256;
+
+fn title_font(statics: &message::Static) -> FontArc {
+    if &statics.game[..] == "kingston" || &statics.game[..] == "bf2042" {
+        FontArc::try_from_slice(include_bytes!("BF_Modernista-Regular.ttf") as &[u8]).unwrap()
+    } else {
+        FontArc::try_from_slice(include_bytes!("Futura.ttf") as &[u8]).unwrap()
     }
-    Ok(String::from("./info_image.jpg"))
+}
+
+fn small_font() -> FontArc {
+    FontArc::try_from_slice(include_bytes!("DejaVuSans.ttf") as &[u8]).unwrap()
+}
+
+/// Area from the given position (as a fraction of the image size) up to the
+/// bottom right of the image, with a small margin on the right.
+fn text_area(img_size: &PxScale, x_div: f32, y_div: f32) -> TextBox {
+    let x = (img_size.x / x_div) as i32;
+    let y = (img_size.y / y_div) as i32;
+    TextBox {
+        x,
+        y,
+        width: (img_size.x as i32 - x - (img_size.x / 20.0) as i32).max(1) as u32,
+        height: (img_size.y as i32 - y).max(1) as u32,
+    }
+}
+
+// discord shows profile banners at a 5:2 aspect ratio
+const BANNER_WIDTH: u32 = 960;
+const BANNER_HEIGHT: u32 = 384;
+
+/// Render the profile banner and save it as `banner.png` in `out_dir`.
+pub fn gen_banner(
+    map: &DynamicImage,
+    status: &ServerInfo,
+    statics: &message::Static,
+    samples: &[i32],
+    out_dir: &str,
+) -> Result<String> {
+    banner_card(map, status, statics, samples).save(format!("{}/banner.png", out_dir))?;
+    Ok(format!("{}/banner.png", out_dir))
+}
+
+/// The map image cropped to the banner ratio with the server name, map, mode,
+/// player count, region and a sparkline of the recent player counts drawn on
+/// top. Used as profile banner and as status widget.
+pub fn banner_card(
+    map: &DynamicImage,
+    status: &ServerInfo,
+    statics: &message::Static,
+    samples: &[i32],
+) -> RgbaImage {
+    let mut banner = Blend(
+        map.resize_to_fill(BANNER_WIDTH, BANNER_HEIGHT, FilterType::Triangle)
+            .brighten(-40)
+            .to_rgba8(),
+    );
+    let white = Rgba([255u8, 255u8, 255u8, 255u8]);
+    let grey = Rgba([200u8, 200u8, 200u8, 255u8]);
+    let font = FontChain::new(title_font(statics));
+    let small_font = FontChain::new(small_font());
+
+    // darken the bottom part so the stats stay readable on bright maps
+    draw_filled_rect_mut(
+        &mut banner,
+        Rect::at(0, 240).of_size(BANNER_WIDTH, BANNER_HEIGHT - 240),
+        Rgba([0u8, 0u8, 0u8, 150u8]),
+    );
+
+    font.draw_fitted(
+        &mut banner,
+        white,
+        PxScale::from(44.0),
+        &status.detailed.server_name,
+        TextBox {
+            x: 32,
+            y: 24,
+            width: BANNER_WIDTH - 64,
+            height: 56,
+        },
+        false,
+    );
+
+    let map_info = match status.detailed.map_mode.is_empty() {
+        true => status.detailed.server_map.clone(),
+        false => format!(
+            "{} - {}",
+            status.detailed.server_map, status.detailed.map_mode
+        ),
+    };
+    small_font.draw_fitted(
+        &mut banner,
+        grey,
+        PxScale::from(30.0),
+        &map_info,
+        TextBox {
+            x: 32,
+            y: 82,
+            width: BANNER_WIDTH - 64,
+            height: 80,
+        },
+        true,
+    );
+
+    let players = format!(
+        "{}/{}{}",
+        status.detailed.current_players,
+        status.detailed.max_players,
+        match status.detailed.in_que.unwrap_or(0) > 0 {
+            true => format!(" [{}]", status.detailed.in_que.unwrap_or(0)),
+            false => "".to_string(),
+        },
+    );
+    font.draw_fitted(
+        &mut banner,
+        white,
+        PxScale::from(64.0),
+        &players,
+        TextBox {
+            x: 32,
+            y: 256,
+            width: 540,
+            height: 72,
+        },
+        false,
+    );
+    small_font.draw_fitted(
+        &mut banner,
+        grey,
+        PxScale::from(26.0),
+        &status.detailed.region,
+        TextBox {
+            x: 32,
+            y: 332,
+            width: 540,
+            height: 36,
+        },
+        false,
+    );
+
+    // sparkline of the previous requests including the current one
+    let mut points = samples.to_vec();
+    points.push(status.detailed.current_players);
+    if points.len() > 1 {
+        let (left, top, width, height) = (600.0, 270.0, 328.0, 80.0);
+        let max = status
+            .detailed
+            .max_players
+            .max(*points.iter().max().unwrap())
+            .max(1) as f32;
+        let step = width / (points.len() - 1) as f32;
+        let coords: Vec<(f32, f32)> = points
+            .iter()
+            .enumerate()
+            .map(|(i, players)| {
+                (
+                    left + step * i as f32,
+                    top + height - height * (*players as f32 / max),
+                )
+            })
+            .collect();
+        draw_line_segment_mut(
+            &mut banner,
+            (left, top + height),
+            (left + width, top + height),
+            Rgba([255u8, 255u8, 255u8, 80u8]),
+        );
+        for pair in coords.windows(2) {
+            // draw a few pixels thick so it stays visible when discord scales it down
+            for offset in [-1.0, 0.0, 1.0] {
+                draw_line_segment_mut(
+                    &mut banner,
+                    (pair[0].0, pair[0].1 + offset),
+                    (pair[1].0, pair[1].1 + offset),
+                    white,
+                );
+            }
+        }
+    }
+
+    banner.0
 }
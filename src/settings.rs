@@ -0,0 +1,222 @@
+use super::{message, store};
+use std::{collections::BTreeMap, env, str::FromStr};
+
+/// Settings that can be changed with `/config set`, as the name and the env
+/// variable they are read from on startup.
+pub const SETTINGS: [(&str, &str); 12] = [
+    ("min_player_amount", "minplayeramount"),
+    ("prev_request_count", "prevrequestcount"),
+    ("started_amount", "startedamount"),
+    ("include_spectators", "include_spectators"),
+    ("mins_between_avatar_change", "mins_between_avatar_change"),
+    ("forecast_presence", "forecast_presence"),
+    ("seeding_cooldown", "seeding_cooldown"),
+    ("admin_absence_mins", "admin_absence_mins"),
+    ("max_kpm", "max_kpm"),
+    ("max_accuracy", "max_accuracy"),
+    ("max_kd", "max_kd"),
+    ("new_account_hours", "new_account_hours"),
+];
+
+/// Current value of a setting.
+pub fn get(statics: &message::Static, name: &str) -> String {
+    match name {
+        "min_player_amount" => statics.min_player_amount.to_string(),
+        "prev_request_count" => statics.amount_of_prev_request.to_string(),
+        "started_amount" => statics.started_amount.to_string(),
+        "include_spectators" => statics.include_spectators.clone(),
+        "mins_between_avatar_change" => statics.mins_between_avatar_change.to_string(),
+        "forecast_presence" => statics.forecast_presence.clone(),
+        "seeding_cooldown" => statics.seeding_cooldown.to_string(),
+        "admin_absence_mins" => statics.admin_absence_mins.to_string(),
+        "max_kpm" => statics.max_kpm.to_string(),
+        "max_accuracy" => statics.max_accuracy.to_string(),
+        "max_kd" => statics.max_kd.to_string(),
+        "new_account_hours" => statics.new_account_hours.to_string(),
+        _ => "".to_string(),
+    }
+}
+
+/// A number that can't be negative.
+fn number<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    at_least(value, T::default())
+}
+
+fn at_least<T: FromStr + PartialOrd>(value: &str, min: T) -> Result<T, String> {
+    match value.trim().parse::<T>() {
+        Ok(number) if number >= min => Ok(number),
+        _ => Err(format!("{} isn't a valid number for this setting", value)),
+    }
+}
+
+fn yes_no(value: &str) -> Result<String, String> {
+    match value.trim().to_lowercase().as_str() {
+        "yes" => Ok("yes".to_string()),
+        "no" => Ok("no".to_string()),
+        _ => Err(format!("{} isn't yes or no", value)),
+    }
+}
+
+/// Change a setting, fails if the value doesn't fit it.
+pub fn set(statics: &mut message::Static, name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "min_player_amount" => statics.min_player_amount = number(value)?,
+        // the alerts need at least one previous request to compare with
+        "prev_request_count" => statics.amount_of_prev_request = at_least(value, 1)?,
+        "started_amount" => statics.started_amount = number(value)?,
+        "include_spectators" => statics.include_spectators = yes_no(value)?,
+        "mins_between_avatar_change" => statics.mins_between_avatar_change = number(value)?,
+        "forecast_presence" => statics.forecast_presence = yes_no(value)?,
+        "seeding_cooldown" => statics.seeding_cooldown = number(value)?,
+        "admin_absence_mins" => statics.admin_absence_mins = number(value)?,
+        "max_kpm" => statics.max_kpm = number(value)?,
+        "max_accuracy" => statics.max_accuracy = number(value)?,
+        "max_kd" => statics.max_kd = number(value)?,
+        "new_account_hours" => statics.new_account_hours = number(value)?,
+        _ => return Err(format!("{} can't be changed", name)),
+    }
+    Ok(())
+}
+
+/// Go back to the env value of a setting, copied as is so a value `set` would
+/// refuse can still be restored.
+pub fn reset(
+    statics: &mut message::Static,
+    defaults: &message::Static,
+    name: &str,
+) -> Result<(), String> {
+    match name {
+        "min_player_amount" => statics.min_player_amount = defaults.min_player_amount,
+        "prev_request_count" => statics.amount_of_prev_request = defaults.amount_of_prev_request,
+        "started_amount" => statics.started_amount = defaults.started_amount,
+        "include_spectators" => statics.include_spectators = defaults.include_spectators.clone(),
+        "mins_between_avatar_change" => {
+            statics.mins_between_avatar_change = defaults.mins_between_avatar_change
+        }
+        "forecast_presence" => statics.forecast_presence = defaults.forecast_presence.clone(),
+        "seeding_cooldown" => statics.seeding_cooldown = defaults.seeding_cooldown,
+        "admin_absence_mins" => statics.admin_absence_mins = defaults.admin_absence_mins,
+        "max_kpm" => statics.max_kpm = defaults.max_kpm,
+        "max_accuracy" => statics.max_accuracy = defaults.max_accuracy,
+        "max_kd" => statics.max_kd = defaults.max_kd,
+        "new_account_hours" => statics.new_account_hours = defaults.new_account_hours,
+        _ => return Err(format!("{} can't be changed", name)),
+    }
+    Ok(())
+}
+
+/// Settings changed with `/config set`, saved to disk on every change so
+/// they are kept after a restart.
+pub struct Overrides {
+    path: String,
+    values: BTreeMap<String, String>,
+}
+
+impl Overrides {
+    pub fn load(path: &str) -> Overrides {
+        Overrides {
+            path: path.to_string(),
+            values: store::load(path),
+        }
+    }
+
+    /// Apply the saved settings on top of the env ones, skipping the values
+    /// that don't fit anymore.
+    pub fn apply(&self, statics: &mut message::Static) {
+        for (name, value) in &self.values {
+            if let Err(e) = set(statics, name, value) {
+                log::error!("Ignoring saved setting {}: {}", name, e);
+            }
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.values.insert(name.to_string(), value.to_string());
+        store::save(&self.path, &self.values);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        if self.values.remove(name).is_some() {
+            store::save(&self.path, &self.values);
+        }
+    }
+
+    /// Where the value of a setting comes from: command, env or default.
+    pub fn source(&self, name: &str, env_name: &str) -> &'static str {
+        if self.values.contains_key(name) {
+            "command"
+        } else if env::var(env_name).is_ok() {
+            "env"
+        } else {
+            "default"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prev_request_count_needs_a_previous_request() {
+        let mut statics = message::Static::default();
+        assert!(set(&mut statics, "prev_request_count", "0").is_err());
+        set(&mut statics, "prev_request_count", "1").unwrap();
+        assert_eq!(statics.amount_of_prev_request, 1);
+    }
+
+    #[test]
+    fn numbers_cant_be_negative() {
+        let mut statics = message::Static::default();
+        let before = get(&statics, "seeding_cooldown");
+        assert!(set(&mut statics, "seeding_cooldown", "-5").is_err());
+        assert!(set(&mut statics, "seeding_cooldown", "soon").is_err());
+        assert_eq!(get(&statics, "seeding_cooldown"), before);
+        set(&mut statics, "seeding_cooldown", "0").unwrap();
+        assert_eq!(statics.seeding_cooldown, 0);
+        set(&mut statics, "max_kd", " 2.5 ").unwrap();
+        assert_eq!(get(&statics, "max_kd"), "2.5");
+    }
+
+    #[test]
+    fn yes_or_no() {
+        let mut statics = message::Static::default();
+        set(&mut statics, "include_spectators", "YES").unwrap();
+        assert_eq!(statics.include_spectators, "yes");
+        assert!(set(&mut statics, "include_spectators", "maybe").is_err());
+        assert_eq!(statics.include_spectators, "yes");
+    }
+
+    #[test]
+    fn reset_to_any_env_value() {
+        let defaults = message::Static {
+            amount_of_prev_request: 0,
+            ..Default::default()
+        };
+        let mut statics = defaults.clone();
+        set(&mut statics, "prev_request_count", "3").unwrap();
+        set(&mut statics, "include_spectators", "yes").unwrap();
+        reset(&mut statics, &defaults, "prev_request_count").unwrap();
+        reset(&mut statics, &defaults, "include_spectators").unwrap();
+        assert_eq!(statics.amount_of_prev_request, 0);
+        assert_eq!(statics.include_spectators, defaults.include_spectators);
+        assert!(reset(&mut statics, &defaults, "token").is_err());
+    }
+
+    #[test]
+    fn unknown_setting() {
+        let mut statics = message::Static::default();
+        assert!(set(&mut statics, "token", "secret").is_err());
+        assert_eq!(get(&statics, "token"), "");
+    }
+
+    #[test]
+    fn every_setting_round_trips() {
+        let mut statics = message::Static::default();
+        for (name, _) in SETTINGS.iter() {
+            let value = get(&statics, name);
+            set(&mut statics, name, &value).unwrap();
+            assert_eq!(get(&statics, name), value, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use super::{
+    accounts, admins, events, forecast, guilds, history, message, notify, players, playtime,
+    rotation, seeding, server_info, settings, stats, watch,
+};
+use chrono::Utc;
+use image::DynamicImage;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{atomic, Mutex, RwLock},
+};
+use tokio::sync::broadcast;
+
+/// Everything the update loop gathered that is also served over http.
+pub struct State {
+    /// the env settings with the ones changed by `/config set`
+    statics: RwLock<message::Static>,
+    /// the settings from the env, to go back to with `/config reset`
+    defaults: message::Static,
+    overrides: RwLock<settings::Overrides>,
+    /// minute timestamp of the last finished update, used for the health check
+    pub last_update: atomic::AtomicI64,
+    /// none when the server couldn't be found on the last update
+    pub status: RwLock<Option<server_info::ServerInfo>>,
+    pub map_image: RwLock<Option<DynamicImage>>,
+    pub history: RwLock<history::History>,
+    pub rotation: RwLock<rotation::Rotation>,
+    pub subscriptions: RwLock<notify::Subscriptions>,
+    pub seeding: Mutex<seeding::Seeding>,
+    pub accounts: RwLock<accounts::Accounts>,
+    /// player list of the last update, for games that have one
+    pub players: RwLock<Option<players::PlayerList>>,
+    pub watchlist: RwLock<watch::Watchlist>,
+    pub stats: Mutex<stats::StatsCache>,
+    pub playtime: RwLock<playtime::Playtime>,
+    pub admins: Mutex<admins::Presence>,
+    /// members that got the playing role, none before the first player list
+    pub playing: Mutex<Option<HashSet<u64>>>,
+    pub guilds: RwLock<guilds::Guilds>,
+    pub events: broadcast::Sender<events::Event>,
+    /// encoded images served over http with the update they were made for
+    pub renders: Mutex<HashMap<String, (i64, Vec<u8>)>>,
+    /// stats lookups and role changes of an update, they run next to the
+    /// update loop
+    pub lookups: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+impl State {
+    pub fn new(defaults: message::Static) -> State {
+        let overrides = settings::Overrides::load(&defaults.settings_file);
+        let mut statics = defaults.clone();
+        overrides.apply(&mut statics);
+        State {
+            last_update: atomic::AtomicI64::new(0),
+            status: RwLock::new(None),
+            map_image: RwLock::new(None),
+            history: RwLock::new(history::History::load(
+                &statics.history_file,
+                statics.history_days,
+            )),
+            rotation: RwLock::new(rotation::Rotation::load(
+                &statics.rotation_file,
+                statics.history_days,
+            )),
+            subscriptions: RwLock::new(notify::Subscriptions::load(&statics.subscriptions_file)),
+            seeding: Mutex::new(seeding::Seeding::default()),
+            accounts: RwLock::new(accounts::Accounts::load(&statics.accounts_file)),
+            players: RwLock::new(None),
+            watchlist: RwLock::new(watch::Watchlist::load(&statics.watchlist_file)),
+            stats: Mutex::new(stats::StatsCache::default()),
+            admins: Mutex::new(admins::Presence::default()),
+            playing: Mutex::new(None),
+            guilds: RwLock::new(guilds::Guilds::load(&statics.guilds_file)),
+            playtime: RwLock::new(playtime::Playtime::load(&statics.playtime_file)),
+            statics: RwLock::new(statics),
+            defaults,
+            overrides: RwLock::new(overrides),
+            events: broadcast::channel(64).0,
+            renders: Mutex::new(HashMap::new()),
+            lookups: Mutex::new(None),
+        }
+    }
+
+    /// The current settings, they can change while the bot runs.
+    pub fn statics(&self) -> message::Static {
+        self.statics.read().unwrap().clone()
+    }
+
+    /// Settings of the guild an interaction came from, the ones of the
+    /// `guild` of the bot in dms.
+    pub fn guild_config(&self, guild: Option<u64>) -> guilds::GuildConfig {
+        let statics = self.statics();
+        self.guilds
+            .read()
+            .unwrap()
+            .config(&statics, guild.unwrap_or(statics.guild))
+    }
+
+    /// Change a setting of the running bot and save it.
+    pub fn set_setting(&self, name: &str, value: &str) -> Result<(), String> {
+        let mut statics = self.statics.write().unwrap();
+        settings::set(&mut statics, name, value)?;
+        self.overrides
+            .write()
+            .unwrap()
+            .set(name, &settings::get(&statics, name));
+        Ok(())
+    }
+
+    /// Go back to the env value of a setting.
+    pub fn reset_setting(&self, name: &str) -> Result<(), String> {
+        settings::reset(&mut self.statics.write().unwrap(), &self.defaults, name)?;
+        self.overrides.write().unwrap().remove(name);
+        Ok(())
+    }
+
+    /// Every setting that can be changed with its value and where it comes
+    /// from.
+    pub fn settings(&self) -> Vec<(&'static str, String, &'static str)> {
+        let statics = self.statics();
+        let overrides = self.overrides.read().unwrap();
+        settings::SETTINGS
+            .iter()
+            .map(|(name, env_name)| {
+                (
+                    *name,
+                    settings::get(&statics, name),
+                    overrides.source(name, env_name),
+                )
+            })
+            .collect()
+    }
+
+    /// Push an event to everyone listening on `/events`.
+    pub fn publish(&self, event: events::Event) {
+        // sending only fails when nobody is listening
+        let _ = self.events.send(event);
+    }
+
+    /// Minutes since the update loop last finished.
+    pub fn minutes_since_update(&self) -> i64 {
+        Utc::now().timestamp() / 60 - self.last_update.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Estimates from the last samples of the history.
+    pub fn forecast(&self) -> forecast::Forecast {
+        let history = self.history.read().unwrap();
+        forecast::forecast(history.since(Utc::now().timestamp() - 60 * 60))
+    }
+
+    /// The last server info, none if the server can't be found or the update
+    /// loop stopped running.
+    pub fn current_status(&self) -> Option<server_info::ServerInfo> {
+        if self.minutes_since_update() > 5 {
+            return None;
+        }
+        self.status.read().unwrap().clone()
+    }
+}
@@ -0,0 +1,224 @@
+use super::{
+    message,
+    players::{Player, PlayerList},
+    server_info::{api_game, client},
+    state::State,
+};
+use anyhow::Result;
+use chrono::Utc;
+use reqwest::Url;
+use serenity::{
+    builder::{CreateEmbed, CreateMessage},
+    client::Context,
+    model::id::ChannelId,
+};
+use std::collections::HashMap;
+
+/// Stats are looked up again after this many seconds.
+const CACHE_SECS: i64 = 12 * 60 * 60;
+/// Most lookups per update, so a full server doesn't cause a burst of
+/// requests. The rest is checked on the next updates.
+const LOOKUPS_PER_UPDATE: usize = 4;
+
+/// The stats used to flag players.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    pub kpm: f64,
+    /// percentage
+    pub accuracy: f64,
+    pub kd: f64,
+    pub seconds_played: i64,
+}
+
+/// Looked up stats per player, none when the lookup failed or the player has
+/// no stats.
+#[derive(Default)]
+pub struct StatsCache {
+    entries: HashMap<String, (i64, Option<Stats>)>,
+}
+
+fn key(player: &Player) -> String {
+    match player.player_id {
+        Some(id) => id.to_string(),
+        None => player.name.to_lowercase(),
+    }
+}
+
+/// Number from the stats response, gametools sometimes formats them like
+/// "12.5%".
+fn number(response: &serde_json::Value, field: &str) -> f64 {
+    match &response[field] {
+        serde_json::Value::Number(number) => number.as_f64().unwrap_or(0.0),
+        serde_json::Value::String(text) => text.trim_end_matches('%').parse().unwrap_or(0.0),
+        _ => 0.0,
+    }
+}
+
+async fn lookup(statics: &message::Static, player: &Player) -> Result<Option<Stats>> {
+    let mut url = Url::parse(
+        &format!(
+            "https://api.gametools.network/{}/stats/",
+            api_game(&statics.game)
+        )[..],
+    )
+    .unwrap();
+    {
+        let mut query = url.query_pairs_mut();
+        match player.player_id {
+            Some(id) => query.append_pair("playerid", &id.to_string()),
+            None => query.append_pair("name", &player.name),
+        };
+        query
+            .append_pair("platform", &statics.platform)
+            .append_pair("format_values", "false");
+    }
+    let response = client()
+        .get(url)
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    if response.get("errors").is_some() {
+        return Ok(None);
+    }
+    Ok(Some(Stats {
+        kpm: number(&response, "killsPerMinute"),
+        accuracy: number(&response, "accuracy"),
+        kd: number(&response, "killDeath"),
+        seconds_played: number(&response, "secondsPlayed") as i64,
+    }))
+}
+
+/// Why the stats look suspicious, empty if they don't.
+fn flags(statics: &message::Static, stats: &Stats) -> Vec<String> {
+    let mut flags = Vec::new();
+    if stats.kpm > statics.max_kpm {
+        flags.push(format!("{:.2} kills per minute", stats.kpm));
+    }
+    if stats.accuracy > statics.max_accuracy {
+        flags.push(format!("{:.1}% accuracy", stats.accuracy));
+    }
+    if stats.seconds_played < statics.new_account_hours * 60 * 60 && stats.kd > statics.max_kd {
+        flags.push(format!(
+            "{:.2} K/D after only {}h played",
+            stats.kd,
+            stats.seconds_played / 3600
+        ));
+    }
+    flags
+}
+
+/// Stats page of the player, the name is encoded so any name gives a working
+/// link.
+fn profile_link(statics: &message::Static, player: &Player) -> String {
+    let mut url = Url::parse("https://gametools.network/stats/").unwrap();
+    {
+        let mut path = url.path_segments_mut().unwrap();
+        path.pop_if_empty().push(&statics.platform);
+        match player.player_id {
+            Some(id) => path.push("playerid").push(&id.to_string()),
+            None => path.push("name").push(&player.name),
+        };
+    }
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("game", api_game(&statics.game));
+        if player.player_id.is_some() {
+            query.append_pair("name", &player.name);
+        }
+    }
+    url.to_string()
+}
+
+async fn alert(ctx: &Context, statics: &message::Static, player: &Player, flags: &[String]) {
+    let embed = CreateEmbed::new()
+        .title(format!("Suspicious stats: {}", player.display_name()))
+        .url(profile_link(statics, player))
+        .description(format!(
+            "{} is on {}\n{}",
+            player.name,
+            statics.server_name,
+            flags
+                .iter()
+                .map(|flag| format!("- {}", flag))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ));
+    if let Err(e) = ChannelId::new(statics.admin_channel)
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await
+    {
+        log::error!("Failed to send stats alert for {}: {:#?}", player.name, e);
+    }
+}
+
+/// Look up the stats of a few players that weren't checked recently and
+/// alert the admins about the suspicious ones.
+pub async fn check(ctx: &Context, state: &State, list: &PlayerList) {
+    let statics = state.statics();
+    if statics.admin_channel == 0 {
+        return;
+    }
+    let now = Utc::now().timestamp();
+    let due: Vec<&Player> = {
+        let mut cache = state.stats.lock().unwrap();
+        cache
+            .entries
+            .retain(|_, (checked, _)| now - *checked < CACHE_SECS);
+        list.all()
+            .filter(|player| !cache.entries.contains_key(&key(player)))
+            .take(LOOKUPS_PER_UPDATE)
+            .collect()
+    };
+
+    for player in due {
+        let stats = match lookup(&statics, player).await {
+            Ok(stats) => stats,
+            Err(e) => {
+                log::error!("Failed to look up stats of {}: {:#?}", player.name, e);
+                None
+            }
+        };
+        if let Some(stats) = &stats {
+            let flags = flags(&statics, stats);
+            if !flags.is_empty() {
+                alert(ctx, &statics, player, &flags).await;
+            }
+        }
+        state
+            .stats
+            .lock()
+            .unwrap()
+            .entries
+            .insert(key(player), (now, stats));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, player_id: Option<u64>) -> Player {
+        Player {
+            name: name.to_string(),
+            rank: None,
+            squad: None,
+            join_time: None,
+            player_id,
+            platoon: None,
+        }
+    }
+
+    #[test]
+    fn profile_links_encode_the_name() {
+        let statics = message::Static::default();
+        assert_eq!(
+            profile_link(&statics, &player("a b#c&d?e", None)),
+            "https://gametools.network/stats/pc/name/a%20b%23c&d%3Fe?game=bf1"
+        );
+        assert_eq!(
+            profile_link(&statics, &player("a b#c&d", Some(42))),
+            "https://gametools.network/stats/pc/playerid/42?game=bf1&name=a+b%23c%26d"
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+
+/// Read a json file, the default value if it doesn't exist yet or can't be
+/// parsed.
+pub fn load<T: DeserializeOwned + Default>(path: &str) -> T {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::error!("Failed to parse {}, starting empty: {:#?}", path, e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Write the value to a json file, errors are only logged so a full disk
+/// doesn't stop the bot.
+pub fn save<T: Serialize>(path: &str, value: &T) {
+    let result = serde_json::to_string_pretty(value)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| fs::write(path, content).map_err(anyhow::Error::from));
+    if let Err(e) = result {
+        log::error!("Failed to save {}: {:#?}", path, e);
+    }
+}
@@ -0,0 +1,341 @@
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use imageproc::{
+    definitions::Clamp,
+    drawing::{draw_text_mut, text_size, Canvas},
+};
+use std::{env, sync::OnceLock};
+
+/// Smallest factor text is shrunk by before it gets wrapped or cut off.
+const MIN_SHRINK: f32 = 0.25;
+
+/// Used when `fallback_fonts` isn't set, the CJK and emoji fonts installed by
+/// the fonts-noto-cjk and fonts-symbola packages of the docker image.
+const DEFAULT_FALLBACK_FONTS: [&str; 2] = [
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/ancient-scripts/Symbola_hint.ttf",
+];
+
+/// Fonts from the `fallback_fonts` env variable (comma separated paths), read
+/// once so large CJK/emoji fonts aren't loaded from disk on every update.
+fn extra_fonts() -> &'static [FontArc] {
+    static FONTS: OnceLock<Vec<FontArc>> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let paths: Vec<String> = match env::var("fallback_fonts") {
+            Ok(paths) => paths
+                .split(',')
+                .map(|path| path.trim().to_string())
+                .collect(),
+            // the defaults are skipped quietly when they aren't installed
+            Err(_) => DEFAULT_FALLBACK_FONTS
+                .iter()
+                .filter(|path| std::path::Path::new(path).exists())
+                .map(|path| path.to_string())
+                .collect(),
+        };
+        paths
+            .iter()
+            .filter(|path| !path.is_empty())
+            .filter_map(|path| match std::fs::read(path) {
+                Ok(data) => match FontArc::try_from_vec(data) {
+                    Ok(font) => Some(font),
+                    Err(e) => {
+                        log::error!("fallback font {} isn't a valid font: {:#?}", path, e);
+                        None
+                    }
+                },
+                Err(e) => {
+                    log::error!("Failed to read fallback font {}: {:#?}", path, e);
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+/// A list of fonts where every character is drawn with the first font that has
+/// a glyph for it, so names with characters missing from the main font don't
+/// render as boxes.
+pub struct FontChain {
+    fonts: Vec<FontArc>,
+}
+
+/// A rectangle text has to fit in.
+#[derive(Clone, Copy, Debug)]
+pub struct TextBox {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FontChain {
+    /// `primary` followed by DejaVuSans and any configured fallback fonts.
+    pub fn new(primary: FontArc) -> FontChain {
+        let mut fonts = vec![
+            primary,
+            FontArc::try_from_slice(include_bytes!("DejaVuSans.ttf") as &[u8]).unwrap(),
+        ];
+        fonts.extend(extra_fonts().iter().cloned());
+        FontChain { fonts }
+    }
+
+    fn font_index(&self, c: char) -> Option<usize> {
+        self.fonts.iter().position(|font| font.glyph_id(c).0 != 0)
+    }
+
+    /// Split text in parts that can be drawn with a single font.
+    fn runs<'a>(&self, text: &'a str) -> Vec<(usize, &'a str)> {
+        let mut runs: Vec<(usize, &str)> = Vec::new();
+        let mut start = 0;
+        let mut current = None;
+        for (i, c) in text.char_indices() {
+            // whitespace exists in every font, keep it in the current run
+            let index = match c.is_whitespace() {
+                true => current.or(Some(0)),
+                false => self.font_index(c).or(current).or(Some(0)),
+            };
+            if let Some(previous) = current.filter(|previous| Some(*previous) != index) {
+                runs.push((previous, &text[start..i]));
+                start = i;
+            }
+            current = index;
+        }
+        if let Some(index) = current {
+            runs.push((index, &text[start..]));
+        }
+        runs
+    }
+
+    /// Width and height of a single line of text.
+    pub fn measure(&self, scale: PxScale, text: &str) -> (u32, u32) {
+        self.runs(text)
+            .iter()
+            .map(|(index, run)| text_size(scale, &self.fonts[*index], run))
+            .fold((0, 0), |(w, h), (run_w, run_h)| (w + run_w, h.max(run_h)))
+    }
+
+    fn line_height(&self, scale: PxScale) -> f32 {
+        self.fonts[0].as_scaled(scale).height()
+    }
+
+    /// Draw a single line of text with its top left at x, y.
+    pub fn draw<C>(
+        &self,
+        canvas: &mut C,
+        color: C::Pixel,
+        x: i32,
+        y: i32,
+        scale: PxScale,
+        text: &str,
+    ) where
+        C: Canvas,
+        <C::Pixel as image::Pixel>::Subpixel: Into<f32> + Clamp<f32>,
+    {
+        let ascent = self.fonts[0].as_scaled(scale).ascent();
+        let mut x = x;
+        for (index, run) in self.runs(text) {
+            let font = &self.fonts[index];
+            // keep the baseline of fallback fonts in line with the main font
+            let offset = (ascent - font.as_scaled(scale).ascent()).round() as i32;
+            draw_text_mut(canvas, color, x, y + offset, scale, font, run);
+            x += text_size(scale, font, run).0 as i32;
+        }
+    }
+
+    /// Break text in lines no wider than `width`, on whitespace where possible.
+    fn wrap(&self, scale: PxScale, text: &str, width: u32) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut line = String::new();
+        for word in text.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_string(),
+                false => format!("{} {}", line, word),
+            };
+            if line.is_empty() || self.measure(scale, &candidate).0 <= width {
+                line = candidate;
+            } else {
+                lines.push(line);
+                line = word.to_string();
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Cut text off with an ellipsis until it fits in `width`.
+    fn truncate(&self, scale: PxScale, text: &str, width: u32) -> String {
+        let mut text = text.to_string();
+        if self.measure(scale, &text).0 <= width {
+            return text;
+        }
+        while !text.is_empty() && self.measure(scale, &format!("{}…", text)).0 > width {
+            text.pop();
+        }
+        format!("{}…", text.trim_end())
+    }
+
+    /// Find the largest scale (up to `scale`) at which the text fits in the
+    /// box, wrapping it over multiple lines if `wrap` is set. Text that doesn't
+    /// fit at the smallest scale gets cut off.
+    pub fn fit(
+        &self,
+        scale: PxScale,
+        text: &str,
+        area: TextBox,
+        wrap: bool,
+    ) -> (PxScale, Vec<String>) {
+        let mut factor = 1.0;
+        loop {
+            let current = PxScale {
+                x: scale.x * factor,
+                y: scale.y * factor,
+            };
+            let line_height = self.line_height(current);
+            let (width, _) = self.measure(current, text);
+            if width <= area.width && line_height <= area.height as f32 {
+                return (current, vec![text.to_string()]);
+            }
+            if wrap {
+                let lines = self.wrap(current, text, area.width);
+                if lines.len() as f32 * line_height <= area.height as f32
+                    && lines
+                        .iter()
+                        .all(|line| self.measure(current, line).0 <= area.width)
+                {
+                    return (current, lines);
+                }
+            }
+            if factor * 0.9 < MIN_SHRINK {
+                let max_lines = match wrap {
+                    true => ((area.height as f32 / line_height) as usize).max(1),
+                    false => 1,
+                };
+                let mut lines = match wrap {
+                    true => self.wrap(current, text, area.width),
+                    false => vec![text.to_string()],
+                };
+                if lines.len() > max_lines {
+                    lines.truncate(max_lines);
+                    let last = lines.pop().unwrap_or_default();
+                    lines.push(format!("{}…", last));
+                }
+                let lines = lines
+                    .iter()
+                    .map(|line| self.truncate(current, line, area.width))
+                    .collect();
+                return (current, lines);
+            }
+            factor *= 0.9;
+        }
+    }
+
+    /// Fit text in the box and draw it from the top left of the box. A single
+    /// line that had to shrink stays vertically centered on where the line
+    /// would have been at the requested scale.
+    pub fn draw_fitted<C>(
+        &self,
+        canvas: &mut C,
+        color: C::Pixel,
+        scale: PxScale,
+        text: &str,
+        area: TextBox,
+        wrap: bool,
+    ) where
+        C: Canvas,
+        <C::Pixel as image::Pixel>::Subpixel: Into<f32> + Clamp<f32>,
+    {
+        let full_height = self.line_height(scale).min(area.height as f32);
+        let (scale, lines) = self.fit(scale, text, area, wrap);
+        let line_height = self.line_height(scale);
+        let top = match lines.len() {
+            1 => area.y + ((full_height - line_height) / 2.0).max(0.0) as i32,
+            _ => area.y,
+        };
+        for (i, line) in lines.iter().enumerate() {
+            let y = top + (line_height * i as f32) as i32;
+            self.draw(canvas, color, area.x, y, scale, line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALE: PxScale = PxScale { x: 40.0, y: 40.0 };
+    const TEXT: &str = "Operation Kaiserschlacht on Amiens tonight";
+
+    fn chain() -> FontChain {
+        FontChain::new(FontArc::try_from_slice(include_bytes!("DejaVuSans.ttf") as &[u8]).unwrap())
+    }
+
+    fn area(width: u32, height: u32) -> TextBox {
+        TextBox {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn fits_without_changes() {
+        let chain = chain();
+        let (scale, lines) = chain.fit(SCALE, "Amiens", area(1000, 100), true);
+        assert_eq!(scale, SCALE);
+        assert_eq!(lines, vec!["Amiens"]);
+    }
+
+    #[test]
+    fn wraps_before_shrinking() {
+        let chain = chain();
+        let (width, _) = chain.measure(SCALE, TEXT);
+        let (scale, lines) = chain.fit(SCALE, TEXT, area(width * 2 / 3, 200), true);
+        assert_eq!(scale, SCALE);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines.join(" "), TEXT);
+    }
+
+    #[test]
+    fn shrinks_when_not_wrapping() {
+        let chain = chain();
+        let (width, _) = chain.measure(SCALE, TEXT);
+        let (scale, lines) = chain.fit(SCALE, TEXT, area(width * 2 / 3, 200), false);
+        assert!(scale.y < SCALE.y);
+        assert_eq!(lines, vec![TEXT]);
+        assert!(chain.measure(scale, TEXT).0 <= width * 2 / 3);
+    }
+
+    #[test]
+    fn shrinks_when_wrapped_lines_are_too_high() {
+        let chain = chain();
+        let (width, _) = chain.measure(SCALE, TEXT);
+        let height = chain.line_height(SCALE) as u32 + 1;
+        let (scale, lines) = chain.fit(SCALE, TEXT, area(width * 2 / 3, height), true);
+        assert!(scale.y < SCALE.y);
+        assert!(lines.len() as f32 * chain.line_height(scale) <= height as f32);
+        assert_eq!(lines.join(" "), TEXT);
+    }
+
+    #[test]
+    fn cuts_off_at_the_smallest_scale() {
+        let chain = chain();
+        let (scale, lines) = chain.fit(SCALE, TEXT, area(40, 100), false);
+        assert!(scale.y >= SCALE.y * MIN_SHRINK);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with('…'));
+        assert!(chain.measure(scale, &lines[0]).0 <= 40);
+    }
+
+    #[test]
+    fn cuts_off_the_lines_that_dont_fit() {
+        let chain = chain();
+        let height = (chain.line_height(SCALE) * MIN_SHRINK) as u32 + 1;
+        let (scale, lines) = chain.fit(SCALE, TEXT, area(60, height), true);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with('…'));
+        assert!(chain.measure(scale, &lines[0]).0 <= 60);
+    }
+}
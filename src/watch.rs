@@ -0,0 +1,152 @@
+use super::{players, state::State, store};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    builder::{CreateEmbed, CreateMessage},
+    client::Context,
+    model::id::{ChannelId, UserId},
+};
+use std::collections::HashSet;
+
+/// Someone watching a player, stored in the watchlist file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Watch {
+    /// player name or persona id
+    pub player: String,
+    pub user: u64,
+    /// post in the watch channel instead of sending the user a dm
+    pub announce: bool,
+}
+
+impl Watch {
+    fn matches(&self, player: &players::Player) -> bool {
+        self.player.eq_ignore_ascii_case(&player.name)
+            || player
+                .player_id
+                .map(|id| id.to_string() == self.player)
+                .unwrap_or(false)
+    }
+}
+
+/// All watched players, saved to disk on every change.
+pub struct Watchlist {
+    path: String,
+    list: Vec<Watch>,
+}
+
+impl Watchlist {
+    pub fn load(path: &str) -> Watchlist {
+        Watchlist {
+            path: path.to_string(),
+            list: store::load(path),
+        }
+    }
+
+    /// Add a watch, replacing the one of the same user for that player.
+    pub fn add(&mut self, watch: Watch) {
+        self.list.retain(|existing| {
+            existing.user != watch.user || !existing.player.eq_ignore_ascii_case(&watch.player)
+        });
+        self.list.push(watch);
+        store::save(&self.path, &self.list);
+    }
+
+    /// Stop watching a player, returns if the user was watching it.
+    pub fn remove(&mut self, user: u64, player: &str) -> bool {
+        let before = self.list.len();
+        self.list.retain(|existing| {
+            existing.user != user || !existing.player.eq_ignore_ascii_case(player)
+        });
+        let removed = self.list.len() != before;
+        if removed {
+            store::save(&self.path, &self.list);
+        }
+        removed
+    }
+
+    pub fn of_user(&self, user: u64) -> Vec<&Watch> {
+        self.list
+            .iter()
+            .filter(|watch| watch.user == user)
+            .collect()
+    }
+}
+
+/// Players in `list` that aren't in `other`.
+fn missing_from<'a>(
+    list: &'a players::PlayerList,
+    other: &players::PlayerList,
+) -> Vec<&'a players::Player> {
+    let names: HashSet<String> = other
+        .all()
+        .map(|player| player.name.to_lowercase())
+        .collect();
+    list.all()
+        .filter(|player| !names.contains(&player.name.to_lowercase()))
+        .collect()
+}
+
+/// Tell the watchers of everyone that joined or left since the previous
+/// player list.
+pub async fn check(
+    ctx: &Context,
+    state: &State,
+    previous: &players::PlayerList,
+    current: &players::PlayerList,
+) {
+    let changes: Vec<(&players::Player, bool)> = missing_from(current, previous)
+        .into_iter()
+        .map(|player| (player, true))
+        .chain(
+            missing_from(previous, current)
+                .into_iter()
+                .map(|player| (player, false)),
+        )
+        .collect();
+    if changes.is_empty() {
+        return;
+    }
+    let watches = state.watchlist.read().unwrap().list.clone();
+    let statics = state.statics();
+    let channel = match statics.watch_channel {
+        0 => statics.message_channel,
+        channel => channel,
+    };
+
+    for (player, joined) in changes {
+        let watchers: Vec<&Watch> = watches
+            .iter()
+            .filter(|watch| watch.matches(player))
+            .collect();
+        if watchers.is_empty() {
+            continue;
+        }
+        let embed = CreateEmbed::new()
+            .title(match joined {
+                true => format!("{} joined", player.display_name()),
+                false => format!("{} left", player.display_name()),
+            })
+            .description(&statics.server_name);
+
+        // one post in the channel, no matter how many admins watch the player
+        if watchers.iter().any(|watch| watch.announce) {
+            if let Err(e) = ChannelId::new(channel)
+                .send_message(&ctx.http, CreateMessage::new().embed(embed.clone()))
+                .await
+            {
+                log::error!(
+                    "Failed to announce watched player {}: {:#?}",
+                    player.name,
+                    e
+                );
+            }
+        }
+        for watch in watchers.iter().filter(|watch| !watch.announce) {
+            if let Err(e) = UserId::new(watch.user)
+                .direct_message(&ctx.http, CreateMessage::new().embed(embed.clone()))
+                .await
+            {
+                log::error!("Failed to dm watcher {}: {:#?}", watch.user, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use super::{
+    events, graph, history, message,
+    server_info::{self, ServerState},
+    state::State,
+    text::FontChain,
+};
+use ab_glyph::{Font, FontArc, PxScale};
+use chrono::Utc;
+use futures_util::{stream, Stream, StreamExt};
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use serde::Deserialize;
+use std::{
+    convert::Infallible,
+    io::Cursor,
+    sync::{atomic, Arc},
+};
+use tokio::sync::broadcast::error::RecvError;
+use warp::{http::StatusCode, hyper::Body, reply::Response, Filter};
+
+#[derive(Deserialize)]
+struct WidgetQuery {
+    style: Option<String>,
+}
+
+fn with_state(
+    state: Arc<State>,
+) -> impl Filter<Extract = (Arc<State>,), Error = Infallible> + Clone {
+    warp::any().map(move || Arc::clone(&state))
+}
+
+fn empty_response(status: StatusCode) -> Response {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Run a render on the blocking threads, so it doesn't hold up the other
+/// requests and the bot.
+async fn blocking(render: impl FnOnce() -> Response + Send + 'static) -> Response {
+    tokio::task::spawn_blocking(render)
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Failed to render: {:#?}", e);
+            empty_response(StatusCode::INTERNAL_SERVER_ERROR)
+        })
+}
+
+/// The encoded image for the key, only rendered again after the next update
+/// so embeds on busy pages don't render on every request.
+fn cached(
+    state: &State,
+    key: &str,
+    render: impl FnOnce() -> Result<Vec<u8>, StatusCode>,
+) -> Result<Vec<u8>, StatusCode> {
+    let update = state.last_update.load(atomic::Ordering::Relaxed);
+    if let Some((rendered, image)) = state.renders.lock().unwrap().get(key) {
+        if *rendered == update {
+            return Ok(image.clone());
+        }
+    }
+    let image = render()?;
+    state
+        .renders
+        .lock()
+        .unwrap()
+        .insert(key.to_string(), (update, image.clone()));
+    Ok(image)
+}
+
+/// Render the status card from the last update, `wide` is the size of the
+/// profile banner and `small` half of that for forum signatures.
+fn render_widget(state: &State, style: &str, format: ImageFormat) -> Result<Vec<u8>, StatusCode> {
+    let status = state.current_status();
+    let map = state.map_image.read().unwrap().clone();
+    let (status, map) = match (status, map) {
+        (Some(status), Some(map)) => (status, map),
+        _ => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    let samples = state.history.read().unwrap().recent_players(3, 60);
+
+    let mut card = DynamicImage::ImageRgba8(server_info::banner_card(
+        &map,
+        &status,
+        &state.statics(),
+        &samples,
+    ));
+    match style {
+        "wide" => {}
+        "small" => card = card.resize(card.width() / 2, card.height() / 2, FilterType::Triangle),
+        _ => return Err(StatusCode::BAD_REQUEST),
+    }
+
+    let mut image = Vec::new();
+    if let Err(e) = card.write_to(&mut Cursor::new(&mut image), format) {
+        log::error!("Failed to encode widget: {:#?}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Ok(image)
+}
+
+fn widget(state: &State, style: &str, format: ImageFormat) -> Response {
+    let key = format!("widget-{}.{}", style, format.extensions_str()[0]);
+    let image = match cached(state, &key, || render_widget(state, style, format)) {
+        Ok(image) => image,
+        Err(status) => return empty_response(status),
+    };
+    let mut response = Response::new(Body::from(image));
+    let headers = response.headers_mut();
+    headers.insert("Content-Type", format.to_mime_type().parse().unwrap());
+    // the image changes at most once per update
+    headers.insert("Cache-Control", "public, max-age=60".parse().unwrap());
+    response
+}
+
+/// Shields.io style badge: the game on the left, players, queue and map on
+/// the right in the color of the server state.
+fn badge(state: &State) -> Response {
+    let label = server_info::api_game(&state.statics().game).to_uppercase();
+    let (message, server_state) = match state.current_status() {
+        Some(status) => (
+            format!(
+                "{}/{}{} {}",
+                status.detailed.current_players,
+                status.detailed.max_players,
+                match status.detailed.in_que.unwrap_or(0) > 0 {
+                    true => format!(" [{}]", status.detailed.in_que.unwrap_or(0)),
+                    false => "".to_string(),
+                },
+                status.detailed.server_map
+            ),
+            ServerState::from_status(&status, &state.statics()),
+        ),
+        None => ("offline".to_string(), ServerState::Offline),
+    };
+
+    // DejaVu Sans has about the same widths as the Verdana the badge asks for
+    let dejavu = FontArc::try_from_slice(include_bytes!("DejaVuSans.ttf") as &[u8]).unwrap();
+    // font-size is the em size, a PxScale the height from descent to ascent
+    let scale = PxScale::from(11.0 * dejavu.height_unscaled() / dejavu.units_per_em().unwrap());
+    let font = FontChain::new(dejavu);
+    let label_text = font.measure(scale, &label).0;
+    let message_text = font.measure(scale, &message).0;
+    let label_width = label_text + 10;
+    let message_width = message_text + 10;
+    let width = label_width + message_width;
+    let (label, message) = (escape(&label), escape(&message));
+
+    let svg = format!(
+        concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">"##,
+            r##"<title>{label}: {message}</title>"##,
+            r##"<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>"##,
+            r##"<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>"##,
+            r##"<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>"##,
+            r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">"##,
+            r##"<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3" textLength="{label_text}">{label}</text><text x="{label_x}" y="14" textLength="{label_text}">{label}</text>"##,
+            r##"<text x="{message_x}" y="15" fill="#010101" fill-opacity=".3" textLength="{message_text}">{message}</text><text x="{message_x}" y="14" textLength="{message_text}">{message}</text>"##,
+            r##"</g></svg>"##,
+        ),
+        width = width,
+        label = label,
+        message = message,
+        label_width = label_width,
+        message_width = message_width,
+        label_text = label_text,
+        message_text = message_text,
+        color = server_state.color(),
+        label_x = label_width as f32 / 2.0,
+        message_x = label_width as f32 + message_width as f32 / 2.0,
+    );
+
+    let mut response = Response::new(Body::from(svg));
+    let headers = response.headers_mut();
+    headers.insert("Content-Type", "image/svg+xml".parse().unwrap());
+    headers.insert("Cache-Control", "public, max-age=60".parse().unwrap());
+    response
+}
+
+/// Inline svg chart of the player count over the given samples, with gaps
+/// where the server was offline or the bot wasn't running.
+fn history_chart(samples: &[history::Sample], start: i64, end: i64) -> String {
+    let (width, height) = (720.0, 160.0);
+    let max = samples
+        .iter()
+        .map(|sample| sample.max_players)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let mut path = String::new();
+    let mut previous: Option<i64> = None;
+    for sample in samples {
+        if sample.state == ServerState::Offline {
+            previous = None;
+            continue;
+        }
+        let x = (sample.timestamp - start) as f64 / (end - start) as f64 * width;
+        let y = height - sample.players as f64 / max * (height - 10.0);
+        let command = match previous {
+            Some(timestamp) if sample.timestamp - timestamp <= 5 * 60 => "L",
+            _ => "M",
+        };
+        path.push_str(&format!("{}{:.1} {:.1} ", command, x, y));
+        previous = Some(sample.timestamp);
+    }
+    format!(
+        concat!(
+            r##"<svg class="chart" viewBox="0 0 {width} {height}" preserveAspectRatio="none">"##,
+            r##"<line x1="0" y1="10" x2="{width}" y2="10" stroke="#444" stroke-dasharray="4"/>"##,
+            r##"<path d="{path}" fill="none" stroke="#4c1" stroke-width="2"/></svg>"##,
+        ),
+        width = width,
+        height = height,
+        path = path.trim_end(),
+    )
+}
+
+/// Card with the current status, the player count of the last 24 hours and
+/// the uptime of a server.
+fn server_card(state: &State) -> String {
+    let now = Utc::now().timestamp();
+    let day_ago = now - 24 * 60 * 60;
+    let (chart, uptime) = {
+        let history = state.history.read().unwrap();
+        let samples = history.since(day_ago);
+        (
+            history_chart(samples, day_ago, now),
+            history::uptime(samples),
+        )
+    };
+    let uptime = match uptime {
+        Some(uptime) => format!("{:.1}%", uptime),
+        None => "unknown".to_string(),
+    };
+
+    match state.current_status() {
+        Some(status) => {
+            let server_state = ServerState::from_status(&status, &state.statics());
+            let detailed = &status.detailed;
+            format!(
+                concat!(
+                    r##"<section><h2>{name}</h2>"##,
+                    r##"<p class="state" style="background:{color}">{state}</p>"##,
+                    r##"<p class="players">{players}/{max_players}{queue}</p>"##,
+                    r##"<p>{map} - {mode} &middot; {region}</p>"##,
+                    r##"{chart}<p>Uptime last 24 hours: {uptime}</p>"##,
+                    r##"<a class="join" href="{link}">Join server</a></section>"##,
+                ),
+                name = escape(&detailed.server_name),
+                color = server_state.color(),
+                state = format!("{:?}", server_state),
+                players = detailed.current_players,
+                max_players = detailed.max_players,
+                queue = match detailed.in_que.unwrap_or(0) > 0 {
+                    true => format!(" [{}]", detailed.in_que.unwrap_or(0)),
+                    false => "".to_string(),
+                },
+                map = escape(&detailed.server_map),
+                mode = escape(&detailed.map_mode),
+                region = escape(&detailed.region),
+                chart = chart,
+                uptime = uptime,
+                link = escape(&message::server_link(
+                    &state.statics(),
+                    &status.game_id.clone().unwrap_or_default()
+                )),
+            )
+        }
+        None => format!(
+            concat!(
+                r##"<section><h2>{name}</h2>"##,
+                r##"<p class="state" style="background:{color}">Offline</p>"##,
+                r##"{chart}<p>Uptime last 24 hours: {uptime}</p></section>"##,
+            ),
+            name = escape(&state.statics().server_name),
+            color = ServerState::Offline.color(),
+            chart = chart,
+            uptime = uptime,
+        ),
+    }
+}
+
+/// Status page of the servers this bot watches, without any external assets
+/// so it can be hosted as is.
+fn status_page(state: &State) -> Response {
+    if &state.statics().status_page[..] != "yes" {
+        return empty_response(StatusCode::NOT_FOUND);
+    }
+    let html = format!(
+        concat!(
+            r##"<!DOCTYPE html><html><head><meta charset="utf-8">"##,
+            r##"<meta name="viewport" content="width=device-width, initial-scale=1">"##,
+            r##"<meta http-equiv="refresh" content="60"><title>{title}</title><style>"##,
+            "body{{background:#16181d;color:#ddd;font-family:sans-serif;margin:0 auto;max-width:760px;padding:16px}}",
+            "section{{background:#22252b;border-radius:8px;margin-bottom:16px;padding:16px 20px}}",
+            "h2{{margin:0 0 8px}}.players{{font-size:32px;margin:8px 0}}",
+            ".state{{border-radius:4px;color:#fff;display:inline-block;margin:0;padding:2px 8px}}",
+            ".chart{{background:#1b1d22;height:160px;width:100%}}",
+            ".join{{background:#4c1;border-radius:4px;color:#fff;display:inline-block;padding:8px 16px;text-decoration:none}}",
+            r##"</style></head><body>{servers}</body></html>"##,
+        ),
+        title = escape(&state.statics().server_name),
+        servers = server_card(state),
+    );
+    let mut response = Response::new(Body::from(html));
+    let headers = response.headers_mut();
+    headers.insert("Content-Type", "text/html; charset=utf-8".parse().unwrap());
+    headers.insert("Cache-Control", "public, max-age=60".parse().unwrap());
+    response
+}
+
+fn sse_event(event: &events::Event) -> warp::sse::Event {
+    warp::sse::Event::default()
+        .event(event.name())
+        .json_data(event)
+        .unwrap_or_default()
+}
+
+/// Live feed of samples, state changes and alerts, starting with the last
+/// sample so listeners don't have to wait for the next update.
+fn event_stream(state: &State) -> impl Stream<Item = Result<warp::sse::Event, Infallible>> {
+    let last = state
+        .history
+        .read()
+        .unwrap()
+        .last()
+        .map(|sample| Ok(sse_event(&events::Event::Sample(sample.clone()))));
+    let live = stream::unfold(state.events.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((Ok(sse_event(&event)), receiver)),
+                // a slow listener missed some events, continue with the newest
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    stream::iter(last).chain(live)
+}
+
+/// Transparent page for OBS browser sources, kept up to date by `/events`.
+fn overlay(state: &State) -> Response {
+    let font = match &state.statics().game[..] {
+        "kingston" | "bf2042" => "BF_Modernista-Regular.ttf",
+        _ => "Futura.ttf",
+    };
+    let html = include_str!("overlay.html")
+        .replace("{{font}}", font)
+        .replace("{{name}}", &escape(&state.statics().server_name));
+    let mut response = Response::new(Body::from(html));
+    response
+        .headers_mut()
+        .insert("Content-Type", "text/html; charset=utf-8".parse().unwrap());
+    response
+}
+
+/// The fonts bundled in the bot, for the overlay.
+fn font(name: String) -> Response {
+    let font: &'static [u8] = match &name[..] {
+        "Futura.ttf" => include_bytes!("Futura.ttf"),
+        "BF_Modernista-Regular.ttf" => include_bytes!("BF_Modernista-Regular.ttf"),
+        _ => return empty_response(StatusCode::NOT_FOUND),
+    };
+    let mut response = Response::new(Body::from(font));
+    let headers = response.headers_mut();
+    headers.insert("Content-Type", "font/ttf".parse().unwrap());
+    headers.insert("Cache-Control", "public, max-age=86400".parse().unwrap());
+    response
+}
+
+pub async fn serve(state: Arc<State>) {
+    let widget_png = warp::path!("widget.png")
+        .and(warp::query::<WidgetQuery>())
+        .and(with_state(Arc::clone(&state)))
+        .then(|query: WidgetQuery, state: Arc<State>| {
+            blocking(move || {
+                widget(
+                    &state,
+                    query.style.as_deref().unwrap_or("wide"),
+                    ImageFormat::Png,
+                )
+            })
+        });
+    let widget_webp = warp::path!("widget.webp")
+        .and(warp::query::<WidgetQuery>())
+        .and(with_state(Arc::clone(&state)))
+        .then(|query: WidgetQuery, state: Arc<State>| {
+            blocking(move || {
+                widget(
+                    &state,
+                    query.style.as_deref().unwrap_or("wide"),
+                    ImageFormat::WebP,
+                )
+            })
+        });
+    let badge = warp::path!("badge.svg")
+        .and(with_state(Arc::clone(&state)))
+        .map(|state: Arc<State>| badge(&state));
+    let events = warp::path!("events")
+        .and(with_state(Arc::clone(&state)))
+        .map(|state: Arc<State>| {
+            warp::sse::reply(warp::sse::keep_alive().stream(event_stream(&state)))
+        });
+    let overlay = warp::path!("overlay")
+        .and(with_state(Arc::clone(&state)))
+        .map(|state: Arc<State>| overlay(&state));
+    let fonts = warp::path!("fonts" / String).map(font);
+    let heatmap = warp::path!("heatmap.png")
+        .and(with_state(Arc::clone(&state)))
+        .then(|state: Arc<State>| {
+            blocking(move || {
+                let png = cached(&state, "heatmap.png", || {
+                    graph::heatmap_png(&state, None).map_err(|e| {
+                        log::error!("Failed to render heatmap: {:#?}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })
+                });
+                match png {
+                    Ok(png) => {
+                        let mut response = Response::new(Body::from(png));
+                        let headers = response.headers_mut();
+                        headers.insert("Content-Type", "image/png".parse().unwrap());
+                        headers.insert("Cache-Control", "public, max-age=3600".parse().unwrap());
+                        response
+                    }
+                    Err(status) => empty_response(status),
+                }
+            })
+        });
+    let status = warp::path!("status")
+        .and(with_state(Arc::clone(&state)))
+        .map(|state: Arc<State>| status_page(&state));
+
+    let hello = with_state(state).map(|state: Arc<State>| {
+        let minutes_since_update = state.minutes_since_update();
+        if minutes_since_update > 5 {
+            warp::reply::with_status(
+                format!("{}", minutes_since_update),
+                StatusCode::SERVICE_UNAVAILABLE,
+            )
+        } else {
+            warp::reply::with_status(format!("{}", minutes_since_update), StatusCode::OK)
+        }
+    });
+
+    warp::serve(
+        widget_png
+            .or(widget_webp)
+            .or(badge)
+            .or(status)
+            .or(events)
+            .or(overlay)
+            .or(fonts)
+            .or(heatmap)
+            .or(hello),
+    )
+    .run(([0, 0, 0, 0], 3030))
+    .await;
+}